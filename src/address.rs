@@ -0,0 +1,188 @@
+use crate::hash;
+use crate::{Script, ScriptType};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl Network {
+    pub fn magic(self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xF9, 0xBE, 0xB4, 0xD9],
+            Network::Testnet => [0x0B, 0x11, 0x09, 0x07],
+            Network::Regtest => [0xFA, 0xBF, 0xB5, 0xDA],
+        }
+    }
+
+    fn p2pkh_version(self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet | Network::Regtest => 0x6F,
+        }
+    }
+
+    fn p2sh_version(self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            Network::Testnet | Network::Regtest => 0xC4,
+        }
+    }
+
+    fn bech32_hrp(self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Address {
+    network: Network,
+    script_type: ScriptType,
+    // The hash or witness program embedded in the scriptPubKey.
+    payload: Vec<u8>,
+}
+
+impl Address {
+    pub fn from_script(script: &Script, network: Network) -> Option<Address> {
+        let script_type = script.classify();
+        let payload = match script_type {
+            ScriptType::P2pkh => script[3..23].to_vec(),
+            ScriptType::P2sh => script[2..22].to_vec(),
+            ScriptType::P2wpkh | ScriptType::P2wsh | ScriptType::P2tr => script[2..].to_vec(),
+            ScriptType::OpReturn | ScriptType::NonStandard => return None,
+        };
+        Some(Address {
+            network,
+            script_type,
+            payload,
+        })
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn script_type(&self) -> ScriptType {
+        self.script_type
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = self.network.bech32_hrp();
+        let encoded = match self.script_type {
+            ScriptType::P2pkh => base58check(self.network.p2pkh_version(), &self.payload),
+            ScriptType::P2sh => base58check(self.network.p2sh_version(), &self.payload),
+            ScriptType::P2wpkh | ScriptType::P2wsh => segwit_address(hrp, 0, &self.payload),
+            ScriptType::P2tr => segwit_address(hrp, 1, &self.payload),
+            ScriptType::OpReturn | ScriptType::NonStandard => {
+                unreachable!("from_script only builds addresses for standard outputs")
+            }
+        };
+        f.write_str(&encoded)
+    }
+}
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn base58check(version: u8, payload: &[u8]) -> String {
+    let mut data = vec![version];
+    data.extend_from_slice(payload);
+    let checksum = hash::sha256d(&data);
+    data.extend_from_slice(&checksum[..4]);
+
+    // Repeated division of the big-endian number by 58, least significant
+    // digit first.
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &data {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let leading_zeros = data.iter().take_while(|&&b| b == 0).count();
+    core::iter::repeat_n('1', leading_zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|&d| BASE58_ALPHABET[d as usize] as char),
+        )
+        .collect()
+}
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2BC830A3;
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATORS: [u32; 5] = [0x3B6A57B2, 0x26508E6D, 0x1EA119FA, 0x3D4233DD, 0x2A1462B3];
+    let mut chk = 1u32;
+    for &value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1FFFFFF) << 5) ^ value as u32;
+        for (i, generator) in GENERATORS.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+// BIP-173 for witness version 0, BIP-350 (bech32m) for later versions.
+fn segwit_address(hrp: &str, version: u8, program: &[u8]) -> String {
+    let mut data = vec![version];
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &byte in program {
+        acc = (acc << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            data.push(((acc >> bits) & 0x1F) as u8);
+        }
+        acc &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        data.push(((acc << (5 - bits)) & 0x1F) as u8);
+    }
+
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 0x1F));
+    values.extend_from_slice(&data);
+    values.extend_from_slice(&[0; 6]);
+    let constant = if version == 0 {
+        BECH32_CONST
+    } else {
+        BECH32M_CONST
+    };
+    let polymod = bech32_polymod(&values) ^ constant;
+    data.extend((0..6).map(|i| ((polymod >> (5 * (5 - i))) & 0x1F) as u8));
+
+    let mut address = format!("{}1", hrp);
+    address.extend(data.iter().map(|&d| BECH32_CHARSET[d as usize] as char));
+    address
+}
@@ -0,0 +1,156 @@
+use crate::hash;
+use crate::{
+    BitcoinError, BitcoinTransaction, CompactSize, decode_exact, read_count_checked, require,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct BlockHash(pub [u8; 32]);
+
+impl BlockHash {
+    pub fn to_rpc_hex(&self) -> String {
+        let mut reversed = self.0;
+        reversed.reverse();
+        hex::encode(reversed)
+    }
+}
+
+impl fmt::Display for BlockHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_rpc_hex())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlockHeader {
+    pub version: u32,
+    pub prev_blockhash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    pub const SIZE: usize = 80;
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.prev_blockhash);
+        bytes.extend_from_slice(&self.merkle_root);
+        bytes.extend_from_slice(&self.time.to_le_bytes());
+        bytes.extend_from_slice(&self.bits.to_le_bytes());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        require(bytes, "block header", 0, Self::SIZE)?;
+        let u32_at =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let mut prev_blockhash = [0u8; 32];
+        prev_blockhash.copy_from_slice(&bytes[4..36]);
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&bytes[36..68]);
+        let header = BlockHeader {
+            version: u32_at(0),
+            prev_blockhash,
+            merkle_root,
+            time: u32_at(68),
+            bits: u32_at(72),
+            nonce: u32_at(76),
+        };
+        Ok((header, Self::SIZE))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn block_hash(&self) -> BlockHash {
+        BlockHash(hash::sha256d(&self.to_bytes()))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Block {
+    pub header: BlockHeader,
+    pub transactions: Vec<BitcoinTransaction>,
+}
+
+impl Block {
+    pub fn new(header: BlockHeader, transactions: Vec<BitcoinTransaction>) -> Self {
+        Block {
+            header,
+            transactions,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        CompactSize::new(self.transactions.len() as u64).encode_into(&mut bytes);
+        for tx in &self.transactions {
+            bytes.extend(tx.to_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let (header, mut offset) = BlockHeader::from_bytes(bytes)?;
+        // Version, two empty counts and lock time.
+        let (tx_count, count_bytes) =
+            read_count_checked(&bytes[offset..], 10).map_err(|e| e.at(offset))?;
+        offset += count_bytes;
+
+        let mut transactions = Vec::new();
+        for _ in 0..tx_count {
+            let (tx, tx_bytes) =
+                BitcoinTransaction::from_bytes(&bytes[offset..]).map_err(|e| e.at(offset))?;
+            transactions.push(tx);
+            offset += tx_bytes;
+        }
+        Ok((Block::new(header, transactions), offset))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn block_hash(&self) -> BlockHash {
+        self.header.block_hash()
+    }
+
+    /// Merkle root over the txids, duplicating the last hash of odd-length
+    /// levels. An empty block yields all zeros.
+    pub fn compute_merkle_root(&self) -> [u8; 32] {
+        let mut level: Vec<[u8; 32]> = self.transactions.iter().map(|tx| tx.txid().0).collect();
+        if level.is_empty() {
+            return [0; 32];
+        }
+        while level.len() > 1 {
+            if !level.len().is_multiple_of(2) {
+                level.push(level[level.len() - 1]);
+            }
+            level = level
+                .chunks_exact(2)
+                .map(|pair| {
+                    let mut concat = pair[0].to_vec();
+                    concat.extend_from_slice(&pair[1]);
+                    hash::sha256d(&concat)
+                })
+                .collect();
+        }
+        level[0]
+    }
+
+    pub fn check_merkle_root(&self) -> bool {
+        self.compute_merkle_root() == self.header.merkle_root
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{
+    BitcoinError, BitcoinTransaction, CompactSize, OutPoint, Script, TransactionInput,
+    TransactionOutput, Witness, decode_exact, read_count_checked, require,
+};
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+// Borrowed counterparts of the consensus types. Scripts and witness items
+// are slices into the buffer being decoded, so parsing allocates nothing per
+// script; the owned from_bytes decoders are built on top of these.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct ScriptRef<'a> {
+    pub bytes: &'a [u8],
+}
+
+impl<'a> ScriptRef<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        ScriptRef { bytes }
+    }
+
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<(Self, usize), BitcoinError> {
+        let (size, size_bytes) = CompactSize::from_bytes(bytes)?;
+        let too_long = || BitcoinError::invalid(0, "script length does not fit in usize");
+        let script_len = usize::try_from(size.value).map_err(|_| too_long())?;
+        let end = size_bytes.checked_add(script_len).ok_or_else(too_long)?;
+        require(bytes, "script", size_bytes, script_len)?;
+        Ok((ScriptRef::new(&bytes[size_bytes..end]), end))
+    }
+
+    pub fn decode_exact(bytes: &'a [u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn to_owned(&self) -> Script {
+        Script::new(self.bytes.to_vec())
+    }
+}
+
+impl Deref for ScriptRef<'_> {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        self.bytes
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct WitnessRef<'a> {
+    pub items: Vec<&'a [u8]>,
+}
+
+impl<'a> WitnessRef<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<(Self, usize), BitcoinError> {
+        let (item_count, mut offset) = read_count_checked(bytes, 1)?;
+        let mut items = Vec::new();
+        for _ in 0..item_count {
+            // Witness items share the length-prefixed layout of a script.
+            let (item, item_bytes) = ScriptRef::from_bytes(&bytes[offset..])
+                .map_err(|e| e.at(offset).in_field("witness item"))?;
+            items.push(item.bytes);
+            offset += item_bytes;
+        }
+        Ok((WitnessRef { items }, offset))
+    }
+
+    pub fn decode_exact(bytes: &'a [u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn to_owned(&self) -> Witness {
+        Witness::new(self.items.iter().map(|item| item.to_vec()).collect())
+    }
+}
+
+impl<'a> Deref for WitnessRef<'a> {
+    type Target = Vec<&'a [u8]>;
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TransactionInputRef<'a> {
+    pub previous_output: OutPoint,
+    pub script_sig: ScriptRef<'a>,
+    pub sequence: u32,
+}
+
+impl<'a> TransactionInputRef<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<(Self, usize), BitcoinError> {
+        let (previous_output, outpoint_bytes) = OutPoint::from_bytes(bytes)?;
+        let (script_sig, script_bytes) =
+            ScriptRef::from_bytes(&bytes[outpoint_bytes..]).map_err(|e| e.at(outpoint_bytes))?;
+        let offset = outpoint_bytes + script_bytes;
+        require(bytes, "sequence", offset, 4)?;
+        let sequence = u32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ]);
+        let input = TransactionInputRef {
+            previous_output,
+            script_sig,
+            sequence,
+        };
+        Ok((input, offset + 4))
+    }
+
+    pub fn decode_exact(bytes: &'a [u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn to_owned(&self) -> TransactionInput {
+        TransactionInput::new(
+            self.previous_output.clone(),
+            self.script_sig.to_owned(),
+            self.sequence,
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TransactionOutputRef<'a> {
+    pub value: u64,
+    pub script_pubkey: ScriptRef<'a>,
+}
+
+impl<'a> TransactionOutputRef<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<(Self, usize), BitcoinError> {
+        require(bytes, "output value", 0, 8)?;
+        let value = u64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ]);
+        let (script_pubkey, script_bytes) =
+            ScriptRef::from_bytes(&bytes[8..]).map_err(|e| e.at(8))?;
+        let output = TransactionOutputRef {
+            value,
+            script_pubkey,
+        };
+        Ok((output, 8 + script_bytes))
+    }
+
+    pub fn decode_exact(bytes: &'a [u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn to_owned(&self) -> TransactionOutput {
+        TransactionOutput::new(self.value, self.script_pubkey.to_owned())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TransactionRef<'a> {
+    pub version: u32,
+    pub inputs: Vec<TransactionInputRef<'a>>,
+    pub outputs: Vec<TransactionOutputRef<'a>>,
+    pub lock_time: u32,
+    pub witnesses: Vec<WitnessRef<'a>>,
+}
+
+impl<'a> TransactionRef<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<(Self, usize), BitcoinError> {
+        require(bytes, "version", 0, 4)?;
+        let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let segwit = bytes.get(4..6) == Some(&[0x00, 0x01]);
+        let mut offset = if segwit { 6 } else { 4 };
+        let (input_count, count_bytes) =
+            read_count_checked(&bytes[offset..], 41).map_err(|e| e.at(offset))?;
+        offset += count_bytes;
+
+        let mut inputs = Vec::new();
+        for _ in 0..input_count {
+            let (input, input_bytes) =
+                TransactionInputRef::from_bytes(&bytes[offset..]).map_err(|e| e.at(offset))?;
+            inputs.push(input);
+            offset += input_bytes;
+        }
+
+        let (output_count, count_bytes) =
+            read_count_checked(&bytes[offset..], 9).map_err(|e| e.at(offset))?;
+        offset += count_bytes;
+
+        let mut outputs = Vec::new();
+        for _ in 0..output_count {
+            let (output, output_bytes) =
+                TransactionOutputRef::from_bytes(&bytes[offset..]).map_err(|e| e.at(offset))?;
+            outputs.push(output);
+            offset += output_bytes;
+        }
+
+        let mut witnesses = Vec::new();
+        if segwit {
+            let witness_start = offset;
+            for _ in 0..inputs.len() {
+                let (witness, witness_bytes) =
+                    WitnessRef::from_bytes(&bytes[offset..]).map_err(|e| e.at(offset))?;
+                witnesses.push(witness);
+                offset += witness_bytes;
+            }
+            if witnesses.iter().all(|witness| witness.is_empty()) {
+                return Err(BitcoinError::invalid(
+                    witness_start,
+                    "superfluous witness record",
+                ));
+            }
+        }
+
+        require(bytes, "lock_time", offset, 4)?;
+        let lock_time = u32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ]);
+        let tx = TransactionRef {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+            witnesses,
+        };
+        Ok((tx, offset + 4))
+    }
+
+    pub fn decode_exact(bytes: &'a [u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn is_segwit(&self) -> bool {
+        // Stacks beyond the input count are never serialized.
+        self.witnesses
+            .iter()
+            .take(self.inputs.len())
+            .any(|stack| !stack.is_empty())
+    }
+
+    pub fn to_owned(&self) -> BitcoinTransaction {
+        let mut tx = BitcoinTransaction::with_outputs(
+            self.version,
+            self.inputs
+                .iter()
+                .map(TransactionInputRef::to_owned)
+                .collect(),
+            self.outputs
+                .iter()
+                .map(TransactionOutputRef::to_owned)
+                .collect(),
+            self.lock_time,
+        );
+        tx.witnesses = self.witnesses.iter().map(WitnessRef::to_owned).collect();
+        tx
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    BitcoinError, BitcoinTransaction, Block, BlockHeader, CompactSize, OutPoint, Script,
+    TransactionInput, TransactionOutput, Witness,
+};
+use alloc::format;
+use alloc::vec::Vec;
+use std::io::{self, Read, Write};
+
+pub trait Encodable {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize>;
+}
+
+pub trait Decodable: Sized {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self>;
+}
+
+fn invalid_data(err: BitcoinError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
+}
+
+fn read_array<R: Read, const N: usize>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+// Reads `len` bytes without trusting `len` for the allocation size.
+fn read_bytes<R: Read>(r: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+impl CompactSize {
+    fn decode_after_prefix<R: Read>(prefix: u8, r: &mut R) -> io::Result<Self> {
+        let mut buf = [prefix, 0, 0, 0, 0, 0, 0, 0, 0];
+        let width = match prefix {
+            0xFD => 2,
+            0xFE => 4,
+            0xFF => 8,
+            _ => 0,
+        };
+        r.read_exact(&mut buf[1..1 + width])?;
+        CompactSize::decode_exact(&buf[..1 + width]).map_err(invalid_data)
+    }
+}
+
+impl Encodable for CompactSize {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        let bytes = self.to_bytes();
+        w.write_all(&bytes)?;
+        Ok(bytes.len())
+    }
+}
+
+impl Decodable for CompactSize {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let [prefix] = read_array(r)?;
+        CompactSize::decode_after_prefix(prefix, r)
+    }
+}
+
+impl Encodable for OutPoint {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        w.write_all(&self.txid.0)?;
+        w.write_all(&self.vout.to_le_bytes())?;
+        Ok(36)
+    }
+}
+
+impl Decodable for OutPoint {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let txid = read_array(r)?;
+        let vout = u32::from_le_bytes(read_array(r)?);
+        Ok(OutPoint::new(txid, vout))
+    }
+}
+
+impl Encodable for Script {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        let len = CompactSize::new(self.bytes.len() as u64).consensus_encode(w)?;
+        w.write_all(&self.bytes)?;
+        Ok(len + self.bytes.len())
+    }
+}
+
+impl Decodable for Script {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let len = CompactSize::consensus_decode(r)?;
+        Ok(Script::new(read_bytes(r, len.value)?))
+    }
+}
+
+impl Encodable for TransactionInput {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        let mut len = self.previous_output.consensus_encode(w)?;
+        len += self.script_sig.consensus_encode(w)?;
+        w.write_all(&self.sequence.to_le_bytes())?;
+        Ok(len + 4)
+    }
+}
+
+impl Decodable for TransactionInput {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let previous_output = OutPoint::consensus_decode(r)?;
+        let script_sig = Script::consensus_decode(r)?;
+        let sequence = u32::from_le_bytes(read_array(r)?);
+        Ok(TransactionInput::new(previous_output, script_sig, sequence))
+    }
+}
+
+impl Encodable for TransactionOutput {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        w.write_all(&self.value.to_le_bytes())?;
+        Ok(8 + self.script_pubkey.consensus_encode(w)?)
+    }
+}
+
+impl Decodable for TransactionOutput {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let value = u64::from_le_bytes(read_array(r)?);
+        let script_pubkey = Script::consensus_decode(r)?;
+        Ok(TransactionOutput::new(value, script_pubkey))
+    }
+}
+
+impl Encodable for Witness {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        let bytes = self.to_bytes();
+        w.write_all(&bytes)?;
+        Ok(bytes.len())
+    }
+}
+
+impl Decodable for Witness {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let count = CompactSize::consensus_decode(r)?;
+        let mut items = Vec::new();
+        for _ in 0..count.value {
+            let len = CompactSize::consensus_decode(r)?;
+            items.push(read_bytes(r, len.value)?);
+        }
+        Ok(Witness::new(items))
+    }
+}
+
+fn encode_list<T: Encodable, W: Write>(items: &[T], w: &mut W) -> io::Result<usize> {
+    let mut len = CompactSize::new(items.len() as u64).consensus_encode(w)?;
+    for item in items {
+        len += item.consensus_encode(w)?;
+    }
+    Ok(len)
+}
+
+fn decode_list<T: Decodable, R: Read>(count: CompactSize, r: &mut R) -> io::Result<Vec<T>> {
+    let mut items = Vec::new();
+    for _ in 0..count.value {
+        items.push(T::consensus_decode(r)?);
+    }
+    Ok(items)
+}
+
+impl Encodable for BitcoinTransaction {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        let segwit = self.is_segwit();
+        w.write_all(&self.version_bytes())?;
+        let mut len = 4;
+        if segwit {
+            w.write_all(&[0x00, 0x01])?;
+            len += 2;
+        }
+        len += encode_list(&self.inputs, w)?;
+        len += encode_list(&self.outputs, w)?;
+        if segwit {
+            for i in 0..self.inputs.len() {
+                len += match self.witnesses.get(i) {
+                    Some(witness) => witness.consensus_encode(w)?,
+                    None => Witness::default().consensus_encode(w)?,
+                };
+            }
+        }
+        w.write_all(&self.lock_time_bytes())?;
+        Ok(len + 4)
+    }
+}
+
+impl Decodable for BitcoinTransaction {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let version = u32::from_le_bytes(read_array(r)?);
+        // A zero input count followed by 0x01 is the segwit marker and flag.
+        let [prefix] = read_array(r)?;
+        let (segwit, inputs, output_count) = if prefix == 0x00 {
+            let [next] = read_array(r)?;
+            if next == 0x01 {
+                let inputs = decode_list(CompactSize::consensus_decode(r)?, r)?;
+                (true, inputs, CompactSize::consensus_decode(r)?)
+            } else {
+                (
+                    false,
+                    Vec::new(),
+                    CompactSize::decode_after_prefix(next, r)?,
+                )
+            }
+        } else {
+            let inputs = decode_list(CompactSize::decode_after_prefix(prefix, r)?, r)?;
+            (false, inputs, CompactSize::consensus_decode(r)?)
+        };
+        let outputs = decode_list(output_count, r)?;
+
+        let mut witnesses = Vec::new();
+        if segwit {
+            for _ in 0..inputs.len() {
+                witnesses.push(Witness::consensus_decode(r)?);
+            }
+            if witnesses.iter().all(|witness| witness.is_empty()) {
+                return Err(invalid_data(BitcoinError::invalid(
+                    0,
+                    "superfluous witness record",
+                )));
+            }
+        }
+
+        let lock_time = u32::from_le_bytes(read_array(r)?);
+        let mut tx = BitcoinTransaction::with_outputs(version, inputs, outputs, lock_time);
+        tx.witnesses = witnesses;
+        Ok(tx)
+    }
+}
+
+impl BitcoinTransaction {
+    pub fn write_length_prefixed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let bytes = self.to_bytes();
+        w.write_all(&CompactSize::new(bytes.len() as u64).to_bytes())?;
+        w.write_all(&bytes)
+    }
+
+    pub fn read_length_prefixed<R: Read>(r: &mut R) -> io::Result<Self> {
+        let len = CompactSize::consensus_decode(r)?;
+        let bytes = read_bytes(r, len.value)?;
+        Self::decode_exact(&bytes).map_err(invalid_data)
+    }
+}
+
+impl Encodable for BlockHeader {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        w.write_all(&self.to_bytes())?;
+        Ok(Self::SIZE)
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let bytes: [u8; BlockHeader::SIZE] = read_array(r)?;
+        Ok(BlockHeader::from_bytes(&bytes)
+            .expect("header buffer is exactly 80 bytes")
+            .0)
+    }
+}
+
+impl Encodable for Block {
+    fn consensus_encode<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        Ok(self.header.consensus_encode(w)? + encode_list(&self.transactions, w)?)
+    }
+}
+
+impl Decodable for Block {
+    fn consensus_decode<R: Read>(r: &mut R) -> io::Result<Self> {
+        let header = BlockHeader::consensus_decode(r)?;
+        let transactions = decode_list(CompactSize::consensus_decode(r)?, r)?;
+        Ok(Block::new(header, transactions))
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{
+    Address, BitcoinTransaction, Instruction, Network, Script, ScriptType, SighashType,
+    decode_script_num,
+};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+// Field names and optional members follow Bitcoin Core's
+// decoderawtransaction output, so the serde JSON has the same shape.
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DecodedTransaction {
+    pub txid: String,
+    // The wtxid; equal to txid for transactions without witness data.
+    pub hash: String,
+    pub version: u32,
+    pub size: usize,
+    pub vsize: usize,
+    pub weight: usize,
+    pub locktime: u32,
+    pub vin: Vec<DecodedInput>,
+    pub vout: Vec<DecodedOutput>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DecodedInput {
+    // Coinbase inputs carry the raw scriptSig here instead of an outpoint.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub coinbase: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub txid: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub vout: Option<u32>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "scriptSig", skip_serializing_if = "Option::is_none")
+    )]
+    pub script_sig: Option<DecodedScriptSig>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "txinwitness", skip_serializing_if = "Vec::is_empty")
+    )]
+    pub witness: Vec<String>,
+    pub sequence: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DecodedScriptSig {
+    pub asm: String,
+    pub hex: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DecodedOutput {
+    // In BTC, as Core reports it.
+    pub value: f64,
+    pub n: u32,
+    #[cfg_attr(feature = "serde", serde(rename = "scriptPubKey"))]
+    pub script_pubkey: DecodedScriptPubKey,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DecodedScriptPubKey {
+    pub asm: String,
+    pub hex: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub address: Option<String>,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub script_type: String,
+}
+
+impl BitcoinTransaction {
+    /// Addresses in the output are encoded for `network`.
+    pub fn to_decoded(&self, network: Network) -> DecodedTransaction {
+        let is_coinbase = self.is_coinbase();
+
+        let vin = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let witness = self
+                    .witnesses
+                    .get(i)
+                    .map(|stack| stack.iter().map(hex::encode).collect())
+                    .unwrap_or_default();
+                let script_hex = hex::encode(&*input.script_sig);
+                if is_coinbase {
+                    return DecodedInput {
+                        coinbase: Some(script_hex),
+                        txid: None,
+                        vout: None,
+                        script_sig: None,
+                        witness,
+                        sequence: input.sequence,
+                    };
+                }
+                DecodedInput {
+                    coinbase: None,
+                    txid: Some(input.previous_output.txid.to_rpc_hex()),
+                    vout: Some(input.previous_output.vout),
+                    script_sig: Some(DecodedScriptSig {
+                        asm: core_asm(&input.script_sig, true),
+                        hex: script_hex,
+                    }),
+                    witness,
+                    sequence: input.sequence,
+                }
+            })
+            .collect();
+
+        let vout = self
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(n, output)| DecodedOutput {
+                value: output.value as f64 / 100_000_000.0,
+                n: n as u32,
+                script_pubkey: decode_script_pubkey(&output.script_pubkey, network),
+            })
+            .collect();
+
+        DecodedTransaction {
+            txid: self.txid().to_rpc_hex(),
+            hash: self.wtxid().to_rpc_hex(),
+            version: self.version,
+            size: self.total_size(),
+            vsize: self.vsize(),
+            weight: self.weight(),
+            locktime: self.lock_time,
+            vin,
+            vout,
+        }
+    }
+}
+
+fn decode_script_pubkey(script: &Script, network: Network) -> DecodedScriptPubKey {
+    let script_type = match script.classify() {
+        ScriptType::P2pkh => "pubkeyhash",
+        ScriptType::P2sh => "scripthash",
+        ScriptType::P2wpkh => "witness_v0_keyhash",
+        ScriptType::P2wsh => "witness_v0_scripthash",
+        ScriptType::P2tr => "witness_v1_taproot",
+        ScriptType::OpReturn => "nulldata",
+        // Core also names templates that ScriptType does not distinguish.
+        ScriptType::NonStandard => match script.as_slice() {
+            [len @ (33 | 65), key @ .., 0xAC] if key.len() == *len as usize => "pubkey",
+            _ if script.required_signatures().is_some() => "multisig",
+            _ if script.witness_program().is_some() => "witness_unknown",
+            _ => "nonstandard",
+        },
+    };
+    DecodedScriptPubKey {
+        asm: core_asm(script, false),
+        hex: hex::encode(&**script),
+        address: Address::from_script(script, network).map(|address| address.to_string()),
+        script_type: script_type.to_string(),
+    }
+}
+
+// Bitcoin Core's ScriptToAsmStr: pushes of up to 4 bytes and OP_1NEGATE..OP_16
+// print as numbers, and with `sighash_decode` a DER signature push prints its
+// sighash type in brackets in place of the trailing byte.
+fn core_asm(script: &Script, sighash_decode: bool) -> String {
+    let sighash_decode = sighash_decode && script.first() != Some(&0x6A);
+    let mut parts = Vec::new();
+    for instruction in script.instructions() {
+        let part = match instruction {
+            Ok(Instruction::PushBytes(data)) if data.len() <= 4 => {
+                decode_script_num(data).unwrap_or_default().to_string()
+            }
+            Ok(Instruction::PushBytes(data)) => match data.split_last() {
+                Some((&flag, sig)) if sighash_decode && is_der_signature(data) => {
+                    match sighash_name(flag) {
+                        Some(name) => format!("{}[{}]", hex::encode(sig), name),
+                        None => hex::encode(data),
+                    }
+                }
+                _ => hex::encode(data),
+            },
+            Ok(Instruction::Op(opcode)) => match opcode as u8 {
+                0x4F => "-1".to_string(),
+                op @ 0x51..=0x60 => (op - 0x50).to_string(),
+                _ => opcode.name().to_string(),
+            },
+            Ok(Instruction::Unknown(_)) => "OP_UNKNOWN".to_string(),
+            Err(_) => {
+                parts.push("[error]".to_string());
+                break;
+            }
+        };
+        parts.push(part);
+    }
+    parts.join(" ")
+}
+
+fn sighash_name(flag: u8) -> Option<&'static str> {
+    Some(match SighashType::from_u32(flag as u32)? {
+        SighashType::All => "ALL",
+        SighashType::None => "NONE",
+        SighashType::Single => "SINGLE",
+        SighashType::AllPlusAnyoneCanPay => "ALL|ANYONECANPAY",
+        SighashType::NonePlusAnyoneCanPay => "NONE|ANYONECANPAY",
+        SighashType::SinglePlusAnyoneCanPay => "SINGLE|ANYONECANPAY",
+    })
+}
+
+// BIP-66 strict DER encoding of a signature with its trailing sighash byte.
+fn is_der_signature(sig: &[u8]) -> bool {
+    let len = sig.len();
+    if !(9..=73).contains(&len) || sig[0] != 0x30 || sig[1] as usize != len - 3 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= len {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 7 != len {
+        return false;
+    }
+    // Each integer is tagged 0x02, non-empty, positive and minimally encoded.
+    let valid_integer = |start: usize, int_len: usize| {
+        sig[start - 2] == 0x02
+            && int_len != 0
+            && sig[start] & 0x80 == 0
+            && !(int_len > 1 && sig[start] == 0 && sig[start + 1] & 0x80 == 0)
+    };
+    valid_integer(4, len_r) && valid_integer(len_r + 6, len_s)
+}
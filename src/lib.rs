@@ -1,16 +1,196 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::Deref;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use std::ops::Deref;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+mod address;
+mod block;
+mod borrowed;
+#[cfg(feature = "std")]
+mod consensus;
+mod decoded;
+mod hash;
+mod message;
+mod opcode;
+mod psbt;
+mod sighash;
+
+pub use address::{Address, Network};
+pub use block::{Block, BlockHash, BlockHeader};
+pub use borrowed::{
+    ScriptRef, TransactionInputRef, TransactionOutputRef, TransactionRef, WitnessRef,
+};
+#[cfg(feature = "std")]
+pub use consensus::{Decodable, Encodable};
+pub use decoded::{
+    DecodedInput, DecodedOutput, DecodedScriptPubKey, DecodedScriptSig, DecodedTransaction,
+};
+pub use message::{Inventory, MessagePayload, NetAddress, NetworkMessage, VersionMessage};
+pub use opcode::Opcode;
+pub use psbt::{Psbt, PsbtInput, PsbtMap, PsbtOutput};
+pub use sighash::{SighashCache, SighashType};
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CompactSize {
     pub value: u64,
 }
 
+// Offsets are absolute positions in the slice handed to the outermost
+// from_bytes call; errors not tied to decoding report offset 0.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BitcoinError {
-    InsufficientBytes,
-    InvalidFormat,
+    InsufficientBytes {
+        // The field that was being read when the input ran out.
+        field: &'static str,
+        offset: usize,
+        needed: usize,
+        available: usize,
+    },
+    InvalidFormat {
+        offset: usize,
+        reason: &'static str,
+    },
+    // A CompactSize that has a shorter encoding, e.g. FD 05 00 for 5.
+    NonCanonicalVarInt {
+        offset: usize,
+    },
+    InvalidWitnessProgram,
+    // Offset of the malformed push within the script.
+    InvalidScript {
+        offset: usize,
+    },
+}
+
+impl BitcoinError {
+    fn insufficient(bytes: &[u8], field: &'static str, offset: usize, needed: usize) -> Self {
+        BitcoinError::InsufficientBytes {
+            field,
+            offset,
+            needed,
+            available: bytes.len().saturating_sub(offset),
+        }
+    }
+
+    fn invalid(offset: usize, reason: &'static str) -> Self {
+        BitcoinError::InvalidFormat { offset, reason }
+    }
+
+    // Rebases an error reported relative to a sub-slice starting at `base`.
+    fn at(self, base: usize) -> Self {
+        match self {
+            BitcoinError::InsufficientBytes {
+                field,
+                offset,
+                needed,
+                available,
+            } => BitcoinError::InsufficientBytes {
+                field,
+                offset: offset + base,
+                needed,
+                available,
+            },
+            BitcoinError::InvalidFormat { offset, reason } => BitcoinError::InvalidFormat {
+                offset: offset + base,
+                reason,
+            },
+            BitcoinError::NonCanonicalVarInt { offset } => BitcoinError::NonCanonicalVarInt {
+                offset: offset + base,
+            },
+            other => other,
+        }
+    }
+
+    fn in_field(self, field: &'static str) -> Self {
+        match self {
+            BitcoinError::InsufficientBytes {
+                offset,
+                needed,
+                available,
+                ..
+            } => BitcoinError::InsufficientBytes {
+                field,
+                offset,
+                needed,
+                available,
+            },
+            other => other,
+        }
+    }
+
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            BitcoinError::InsufficientBytes { offset, .. }
+            | BitcoinError::InvalidFormat { offset, .. }
+            | BitcoinError::NonCanonicalVarInt { offset }
+            | BitcoinError::InvalidScript { offset } => Some(*offset),
+            BitcoinError::InvalidWitnessProgram => None,
+        }
+    }
+}
+
+impl fmt::Display for BitcoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitcoinError::InsufficientBytes {
+                field,
+                offset,
+                needed,
+                available,
+            } => write!(
+                f,
+                "truncated {} at offset {}: needed {} bytes, {} available",
+                field, offset, needed, available
+            ),
+            BitcoinError::InvalidFormat { offset, reason } => {
+                write!(f, "invalid format at offset {}: {}", offset, reason)
+            }
+            BitcoinError::NonCanonicalVarInt { offset } => {
+                write!(f, "non-canonical CompactSize at offset {}", offset)
+            }
+            BitcoinError::InvalidWitnessProgram => f.write_str("invalid witness program"),
+            BitcoinError::InvalidScript { offset } => {
+                write!(f, "malformed push at script offset {}", offset)
+            }
+        }
+    }
+}
+
+impl core::error::Error for BitcoinError {}
+
+fn require(
+    bytes: &[u8],
+    field: &'static str,
+    offset: usize,
+    needed: usize,
+) -> Result<(), BitcoinError> {
+    match offset.checked_add(needed) {
+        Some(end) if end <= bytes.len() => Ok(()),
+        _ => Err(BitcoinError::insufficient(bytes, field, offset, needed)),
+    }
+}
+
+fn decode_exact<'a, T, F>(bytes: &'a [u8], from_bytes: F) -> Result<T, BitcoinError>
+where
+    F: FnOnce(&'a [u8]) -> Result<(T, usize), BitcoinError>,
+{
+    let (value, consumed) = from_bytes(bytes)?;
+    if consumed != bytes.len() {
+        return Err(BitcoinError::invalid(consumed, "trailing bytes"));
+    }
+    Ok(value)
 }
 
 impl CompactSize {
@@ -19,62 +199,113 @@ impl CompactSize {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(9);
+        self.encode_into(&mut bytes);
+        bytes
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        match self.value {
+            0..=0xFC => 1,
+            0xFD..=0xFFFF => 3,
+            0x10000..=0xFFFFFFFF => 5,
+            _ => 9,
+        }
+    }
+
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
         match self.value {
-            v if v <= 0xFC => vec![v as u8],
+            v if v <= 0xFC => out.push(v as u8),
             v if v <= 0xFFFF => {
-                let mut bytes = vec![0xFD];
-                bytes.extend_from_slice(&(v as u16).to_le_bytes());
-                bytes
+                out.push(0xFD);
+                out.extend_from_slice(&(v as u16).to_le_bytes());
             }
             v if v <= 0xFFFFFFFF => {
-                let mut bytes = vec![0xFE];
-                bytes.extend_from_slice(&(v as u32).to_le_bytes());
-                bytes
+                out.push(0xFE);
+                out.extend_from_slice(&(v as u32).to_le_bytes());
             }
             v => {
-                let mut bytes = vec![0xFF];
-                bytes.extend_from_slice(&v.to_le_bytes());
-                bytes
+                out.push(0xFF);
+                out.extend_from_slice(&v.to_le_bytes());
             }
         }
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.is_empty() {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+        require(bytes, "CompactSize", 0, 1)?;
 
         match bytes[0] {
             0xFD => {
-                if bytes.len() < 3 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
+                require(bytes, "CompactSize", 0, 3)?;
                 let value = u16::from_le_bytes([bytes[1], bytes[2]]);
+                if value <= 0xFC {
+                    return Err(BitcoinError::NonCanonicalVarInt { offset: 0 });
+                }
                 Ok((CompactSize::new(value as u64), 3))
             }
             0xFE => {
-                if bytes.len() < 5 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
+                require(bytes, "CompactSize", 0, 5)?;
                 let value = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+                if value <= 0xFFFF {
+                    return Err(BitcoinError::NonCanonicalVarInt { offset: 0 });
+                }
                 Ok((CompactSize::new(value as u64), 5))
             }
             0xFF => {
-                if bytes.len() < 9 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
+                require(bytes, "CompactSize", 0, 9)?;
                 let value = u64::from_le_bytes([
                     bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7], bytes[8],
                 ]);
+                if value <= 0xFFFFFFFF {
+                    return Err(BitcoinError::NonCanonicalVarInt { offset: 0 });
+                }
                 Ok((CompactSize::new(value), 9))
             }
             x => Ok((CompactSize::new(x as u64), 1)),
         }
     }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    /// Length of the CompactSize at the start of `bytes`. from_bytes is
+    /// already strict, so a non-minimal encoding fails with
+    /// NonCanonicalVarInt.
+    pub fn validate_encoding(bytes: &[u8]) -> Result<usize, BitcoinError> {
+        CompactSize::from_bytes(bytes).map(|(_, consumed)| consumed)
+    }
 }
-#[derive(Debug, PartialEq, Eq, Clone)]
+pub fn read_count_checked(
+    bytes: &[u8],
+    min_item_size: usize,
+) -> Result<(u64, usize), BitcoinError> {
+    let (count, consumed) = CompactSize::from_bytes(bytes)?;
+    let remaining = (bytes.len() - consumed) as u64;
+    match count.value.checked_mul(min_item_size as u64) {
+        Some(needed) if needed <= remaining => Ok((count.value, consumed)),
+        _ => Err(BitcoinError::invalid(0, "count exceeds remaining bytes")),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct Txid(pub [u8; 32]);
 
+impl Txid {
+    pub fn to_rpc_hex(&self) -> String {
+        let mut reversed = self.0;
+        reversed.reverse();
+        hex::encode(reversed)
+    }
+}
+
+impl fmt::Display for Txid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_rpc_hex())
+    }
+}
+
+#[cfg(feature = "serde")]
 impl Serialize for Txid {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -84,6 +315,7 @@ impl Serialize for Txid {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de> Deserialize<'de> for Txid {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -105,7 +337,25 @@ impl<'de> Deserialize<'de> for Txid {
             })
     }
 }
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Wtxid(pub [u8; 32]);
+
+impl Wtxid {
+    pub fn to_rpc_hex(&self) -> String {
+        let mut reversed = self.0;
+        reversed.reverse();
+        hex::encode(reversed)
+    }
+}
+
+impl fmt::Display for Wtxid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_rpc_hex())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OutPoint {
     pub txid: Txid,
     pub vout: u32,
@@ -126,39 +376,237 @@ impl OutPoint {
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.len() < 36 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+        require(bytes, "outpoint", 0, 36)?;
         let mut txid = [0u8; 32];
         txid.copy_from_slice(&bytes[0..32]);
         let vout = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
         Ok((OutPoint::new(txid, vout), 36))
     }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
 }
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Script {
     pub bytes: Vec<u8>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    OpReturn,
+    NonStandard,
+}
+
 impl Script {
     pub fn new(bytes: Vec<u8>) -> Self {
         Script { bytes }
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = CompactSize::new(self.bytes.len() as u64).to_bytes();
+        let mut bytes = Vec::with_capacity(9 + self.bytes.len());
+        CompactSize::new(self.bytes.len() as u64).encode_into(&mut bytes);
         bytes.extend_from_slice(&self.bytes);
         bytes
     }
 
+    pub fn serialized_size(&self) -> usize {
+        CompactSize::new(self.bytes.len() as u64).encoded_len() + self.bytes.len()
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let (size, size_bytes) = CompactSize::from_bytes(bytes)?;
-        let script_len = size.value as usize;
-        if bytes.len() < size_bytes + script_len {
-            return Err(BitcoinError::InsufficientBytes);
+        ScriptRef::from_bytes(bytes).map(|(script, consumed)| (script.to_owned(), consumed))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn witness_program(&self) -> Option<(u8, Vec<u8>)> {
+        self.witness_program_strict().ok().flatten()
+    }
+
+    pub fn witness_program_strict(&self) -> Result<Option<(u8, Vec<u8>)>, BitcoinError> {
+        let version = match self.bytes.first() {
+            Some(0x00) => 0,
+            Some(op @ 0x51..=0x60) => op - 0x50,
+            _ => return Ok(None),
+        };
+        let push_len = match self.bytes.get(1) {
+            Some(&len @ 0x01..=0x4B) if self.bytes.len() == len as usize + 2 => len as usize,
+            _ => return Ok(None),
+        };
+        if !(2..=40).contains(&push_len) {
+            return Err(BitcoinError::InvalidWitnessProgram);
+        }
+        Ok(Some((version, self.bytes[2..].to_vec())))
+    }
+
+    pub fn is_burn(&self) -> bool {
+        // hash160 behind 1BitcoinEaterAddressDontSendf59kuE
+        const BITCOIN_EATER: [u8; 20] = [
+            0x75, 0x9d, 0x66, 0x77, 0x09, 0x1e, 0x97, 0x3b, 0x9e, 0x9d, 0x99, 0xf1, 0x9c, 0x68,
+            0xfb, 0xf4, 0x3e, 0x3f, 0x05, 0xf9,
+        ];
+        let b = &self.bytes;
+        let is_p2pkh = b.len() == 25 && b[..3] == [0x76, 0xA9, 0x14] && b[23..] == [0x88, 0xAC];
+        b.first() == Some(&0x6A) || (is_p2pkh && b[3..23] == BITCOIN_EATER)
+    }
+
+    pub fn classify(&self) -> ScriptType {
+        match self.bytes.as_slice() {
+            [0x76, 0xA9, 0x14, hash @ .., 0x88, 0xAC] if hash.len() == 20 => ScriptType::P2pkh,
+            [0xA9, 0x14, hash @ .., 0x87] if hash.len() == 20 => ScriptType::P2sh,
+            [0x00, 0x14, program @ ..] if program.len() == 20 => ScriptType::P2wpkh,
+            [0x00, 0x20, program @ ..] if program.len() == 32 => ScriptType::P2wsh,
+            [0x51, 0x20, program @ ..] if program.len() == 32 => ScriptType::P2tr,
+            [0x6A, ..] => ScriptType::OpReturn,
+            _ => ScriptType::NonStandard,
+        }
+    }
+
+    pub fn is_standard(&self) -> bool {
+        self.classify() != ScriptType::NonStandard
+    }
+
+    pub fn required_signatures(&self) -> Option<usize> {
+        let b = &self.bytes;
+        let is_p2pkh = b.len() == 25 && b[..3] == [0x76, 0xA9, 0x14] && b[23..] == [0x88, 0xAC];
+        let is_p2wpkh = b.len() == 22 && b[..2] == [0x00, 0x14];
+        if is_p2pkh || is_p2wpkh {
+            return Some(1);
+        }
+
+        // OP_m <pubkey>... OP_n OP_CHECKMULTISIG
+        let ops: Vec<_> = self.raw_instructions().collect::<Result<_, _>>().ok()?;
+        let small_int = |op: u8| (0x51..=0x60).contains(&op).then(|| (op - 0x50) as usize);
+        let (&(first, _), rest) = ops.split_first()?;
+        let (&(last, _), rest) = rest.split_last()?;
+        let (&(count_op, _), keys) = rest.split_last()?;
+        let m = small_int(first)?;
+        let n = small_int(count_op)?;
+        let keys_valid = keys
+            .iter()
+            .all(|(_, data)| matches!(data, Some(key) if key.len() == 33 || key.len() == 65));
+        if last != 0xAE || keys.len() != n || !keys_valid || m > n {
+            return None;
+        }
+        Some(m)
+    }
+
+    /// Sigop count: one per OP_CHECKSIG(VERIFY) and 20 per
+    /// OP_CHECKMULTISIG(VERIFY). With `accurate`, as for P2SH redeem and
+    /// witness scripts, a multisig preceded by OP_1..OP_16 counts that many
+    /// instead; scriptSigs and scriptPubKeys are counted without it.
+    /// Counting stops at the first malformed push.
+    pub fn sigop_count(&self, accurate: bool) -> usize {
+        let mut count = 0;
+        let mut prev = None;
+        for (opcode, data) in self.raw_instructions().map_while(Result::ok) {
+            count += match (opcode, data) {
+                (0xAC | 0xAD, None) => 1,
+                (0xAE | 0xAF, None) => match prev {
+                    Some(op @ 0x51..=0x60) if accurate => (op - 0x50) as usize,
+                    _ => 20,
+                },
+                _ => 0,
+            };
+            prev = Some(opcode);
+        }
+        count
+    }
+
+    /// True if the script holds only push opcodes (up to OP_16) and parses.
+    pub fn is_push_only(&self) -> bool {
+        self.raw_instructions()
+            .all(|op| matches!(op, Ok((opcode, _)) if opcode <= 0x60))
+    }
+
+    /// True if any push uses a larger opcode than necessary, following
+    /// Core's CheckMinimalPush.
+    pub fn has_non_minimal_push(&self) -> bool {
+        self.raw_instructions()
+            .map_while(Result::ok)
+            .any(|(opcode, data)| data.is_some_and(|data| !is_minimal_push(opcode, data)))
+    }
+
+    /// True if any push of up to 4 bytes is not a minimally encoded script
+    /// number. Only meaningful for pushes consumed as numbers; ordinary data
+    /// such as a single 0x00 byte is flagged too.
+    pub fn has_non_minimal_number(&self) -> bool {
+        self.raw_instructions()
+            .map_while(Result::ok)
+            .any(|(_, data)| {
+                data.is_some_and(|data| data.len() <= 4 && !is_minimal_script_num(data))
+            })
+    }
+
+    pub fn has_timelock_opcode(&self) -> bool {
+        self.raw_instructions()
+            .map_while(Result::ok)
+            .any(|(opcode, data)| data.is_none() && matches!(opcode, 0xB1 | 0xB2))
+    }
+
+    pub fn extract_timelock(&self) -> Option<i64> {
+        let ops: Vec<_> = self.raw_instructions().map_while(Result::ok).collect();
+        ops.windows(2).find_map(|pair| match pair {
+            [(_, Some(data)), (0xB1 | 0xB2, None)] => decode_script_num(data),
+            [(0x4F, None), (0xB1 | 0xB2, None)] => Some(-1),
+            [(op @ 0x51..=0x60, None), (0xB1 | 0xB2, None)] => Some((op - 0x50) as i64),
+            _ => None,
+        })
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = Result<Instruction<'_>, BitcoinError>> {
+        self.raw_instructions().map(|op| {
+            op.map(|(opcode, data)| match data {
+                Some(data) => Instruction::PushBytes(data),
+                None => Opcode::from_byte(opcode)
+                    .map(Instruction::Op)
+                    .unwrap_or(Instruction::Unknown(opcode)),
+            })
+        })
+    }
+
+    pub fn to_asm(&self) -> String {
+        let mut parts = Vec::new();
+        for instruction in self.instructions() {
+            parts.push(match instruction {
+                Ok(Instruction::PushBytes([])) => "OP_0".to_string(),
+                Ok(Instruction::PushBytes(data)) => hex::encode(data),
+                Ok(Instruction::Op(opcode)) => opcode.name().to_string(),
+                Ok(Instruction::Unknown(_)) => "OP_UNKNOWN".to_string(),
+                Err(_) => "[error]".to_string(),
+            });
+        }
+        parts.join(" ")
+    }
+
+    fn without_codeseparators(&self) -> Script {
+        let mut instructions = self.raw_instructions();
+        let mut bytes = Vec::with_capacity(self.bytes.len());
+        let mut start = 0;
+        while let Some(op) = instructions.next() {
+            let end = instructions.pos;
+            if !matches!(op, Ok((0xAB, None))) {
+                bytes.extend_from_slice(&self.bytes[start..end]);
+            }
+            start = end;
+        }
+        Script::new(bytes)
+    }
+
+    fn raw_instructions(&self) -> RawInstructions<'_> {
+        RawInstructions {
+            bytes: &self.bytes,
+            pos: 0,
         }
-        let script_bytes = bytes[size_bytes..size_bytes + script_len].to_vec();
-        Ok((Script::new(script_bytes), size_bytes + script_len))
     }
 }
 impl Deref for Script {
@@ -168,7 +616,107 @@ impl Deref for Script {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_asm())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction<'a> {
+    // Data pushed by OP_0, a direct push or OP_PUSHDATA1/2/4.
+    PushBytes(&'a [u8]),
+    Op(Opcode),
+    Unknown(u8),
+}
+
+// Walks a script yielding each opcode together with its pushed data, if any.
+struct RawInstructions<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Iterator for RawInstructions<'a> {
+    type Item = Result<(u8, Option<&'a [u8]>), BitcoinError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let opcode = *self.bytes.get(self.pos)?;
+        let op_start = self.pos;
+        self.pos += 1;
+
+        let (len_bytes, push_len) = match opcode {
+            0x00..=0x4B => (0, opcode as usize),
+            0x4C..=0x4E => {
+                let width = match opcode {
+                    0x4C => 1,
+                    0x4D => 2,
+                    _ => 4,
+                };
+                let Some(raw) = self.bytes.get(self.pos..self.pos + width) else {
+                    self.pos = self.bytes.len();
+                    return Some(Err(BitcoinError::InvalidScript { offset: op_start }));
+                };
+                let mut len = [0u8; 4];
+                len[..width].copy_from_slice(raw);
+                (width, u32::from_le_bytes(len) as usize)
+            }
+            _ => return Some(Ok((opcode, None))),
+        };
+
+        let start = self.pos + len_bytes;
+        let end = start.saturating_add(push_len);
+        let Some(data) = self.bytes.get(start..end) else {
+            self.pos = self.bytes.len();
+            return Some(Err(BitcoinError::InvalidScript { offset: op_start }));
+        };
+        self.pos = end;
+        Some(Ok((opcode, Some(data))))
+    }
+}
+
+fn is_minimal_push(opcode: u8, data: &[u8]) -> bool {
+    match data {
+        [] => opcode == 0x00,
+        [n @ 1..=16] => opcode == 0x50 + n,
+        [0x81] => opcode == 0x4F,
+        _ if data.len() <= 75 => opcode as usize == data.len(),
+        _ if data.len() <= 0xFF => opcode == 0x4C,
+        _ if data.len() <= 0xFFFF => opcode == 0x4D,
+        _ => true,
+    }
+}
+
+fn is_minimal_script_num(data: &[u8]) -> bool {
+    match data {
+        [] => true,
+        [.., last] if last & 0x7F != 0 => true,
+        [_] => false,
+        [.., prev, _] => prev & 0x80 != 0,
+    }
+}
+
+// CLTV and CSV operands may be up to 5 bytes, unlike the usual 4-byte limit.
+fn decode_script_num(data: &[u8]) -> Option<i64> {
+    if data.len() > 5 {
+        return None;
+    }
+    let Some((&last, _)) = data.split_last() else {
+        return Some(0);
+    };
+    let magnitude = data
+        .iter()
+        .rev()
+        .fold(0i64, |acc, &b| (acc << 8) | b as i64);
+    if last & 0x80 != 0 {
+        let sign_bit = 0x80i64 << (8 * (data.len() - 1));
+        Some(-(magnitude & !sign_bit))
+    } else {
+        Some(magnitude)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TransactionInput {
     pub previous_output: OutPoint,
     pub script_sig: Script,
@@ -191,104 +739,1381 @@ impl TransactionInput {
         bytes
     }
 
+    pub fn serialized_size(&self) -> usize {
+        36 + self.script_sig.serialized_size() + 4
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let (previous_output, outpoint_bytes) = OutPoint::from_bytes(bytes)?;
-        let (script_sig, script_bytes) = Script::from_bytes(&bytes[outpoint_bytes..])?;
-
-        if bytes.len() < outpoint_bytes + script_bytes + 4 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
-        let sequence = u32::from_le_bytes([
-            bytes[outpoint_bytes + script_bytes],
-            bytes[outpoint_bytes + script_bytes + 1],
-            bytes[outpoint_bytes + script_bytes + 2],
-            bytes[outpoint_bytes + script_bytes + 3],
-        ]);
-        Ok((
-            Self::new(previous_output, script_sig, sequence),
-            outpoint_bytes + script_bytes + 4,
-        ))
+        TransactionInputRef::from_bytes(bytes).map(|(input, consumed)| (input.to_owned(), consumed))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransactionOutput {
+    pub value: u64,
+    pub script_pubkey: Script,
+}
+
+impl TransactionOutput {
+    pub fn new(value: u64, script_pubkey: Script) -> Self {
+        TransactionOutput {
+            value,
+            script_pubkey,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.value.to_le_bytes().to_vec();
+        bytes.extend(self.script_pubkey.to_bytes());
+        bytes
+    }
+
+    pub fn serialized_size(&self) -> usize {
+        8 + self.script_pubkey.serialized_size()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        TransactionOutputRef::from_bytes(bytes)
+            .map(|(output, consumed)| (output.to_owned(), consumed))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn is_dust(&self, dust_relay_fee: u64) -> bool {
+        let script = &self.script_pubkey;
+        if script.first() == Some(&0x6A) || script.len() > 10_000 {
+            return false;
+        }
+        // Size of the output plus the input that would later spend it, as in
+        // Bitcoin Core's GetDustThreshold.
+        let spend_size = if script.witness_program().is_some() {
+            32 + 4 + 1 + 107 / 4 + 4
+        } else {
+            32 + 4 + 1 + 107 + 4
+        };
+        let threshold = (self.serialized_size() + spend_size) as u64 * dust_relay_fee / 1000;
+        self.value < threshold
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Witness {
+    pub items: Vec<Vec<u8>>,
+}
+
+impl Witness {
+    pub fn new(items: Vec<Vec<u8>>) -> Self {
+        Witness { items }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.serialized_size());
+        CompactSize::new(self.items.len() as u64).encode_into(&mut bytes);
+        for item in &self.items {
+            CompactSize::new(item.len() as u64).encode_into(&mut bytes);
+            bytes.extend_from_slice(item);
+        }
+        bytes
+    }
+
+    pub fn serialized_size(&self) -> usize {
+        let items: usize = self
+            .items
+            .iter()
+            .map(|item| CompactSize::new(item.len() as u64).encoded_len() + item.len())
+            .sum();
+        CompactSize::new(self.items.len() as u64).encoded_len() + items
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        WitnessRef::from_bytes(bytes).map(|(witness, consumed)| (witness.to_owned(), consumed))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+}
+
+impl Deref for Witness {
+    type Target = Vec<Vec<u8>>;
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BitcoinTransaction {
     pub version: u32,
     pub inputs: Vec<TransactionInput>,
+    pub outputs: Vec<TransactionOutput>,
     pub lock_time: u32,
+    pub witnesses: Vec<Witness>,
 }
 
 impl BitcoinTransaction {
     pub fn new(version: u32, inputs: Vec<TransactionInput>, lock_time: u32) -> Self {
+        Self::with_outputs(version, inputs, Vec::new(), lock_time)
+    }
+
+    pub fn with_outputs(
+        version: u32,
+        inputs: Vec<TransactionInput>,
+        outputs: Vec<TransactionOutput>,
+        lock_time: u32,
+    ) -> Self {
         BitcoinTransaction {
             version,
             inputs,
+            outputs,
             lock_time,
+            witnesses: Vec::new(),
         }
     }
 
+    pub fn is_segwit(&self) -> bool {
+        // Stacks beyond the input count are never serialized.
+        self.witnesses
+            .iter()
+            .take(self.inputs.len())
+            .any(|stack| !stack.is_empty())
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_with_flag(false)
+    }
+
+    /// With `force_witness`, emits the marker, flag and witness section even
+    /// when every stack is empty.
+    pub fn to_bytes_with_flag(&self, force_witness: bool) -> Vec<u8> {
+        if !force_witness && !self.is_segwit() {
+            return self.to_legacy_bytes();
+        }
         let mut bytes = self.version.to_le_bytes().to_vec();
-        bytes.extend(CompactSize::new(self.inputs.len() as u64).to_bytes());
-        for input in &self.inputs {
-            bytes.extend(input.to_bytes());
+        bytes.extend_from_slice(&[0x00, 0x01]);
+        self.encode_inputs_and_outputs(&mut bytes);
+        for i in 0..self.inputs.len() {
+            bytes.extend(self.witness_bytes(i));
         }
         bytes.extend_from_slice(&self.lock_time.to_le_bytes());
         bytes
     }
 
+    /// Non-witness serialization; this is the txid preimage.
+    pub fn to_legacy_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.version.to_le_bytes().to_vec();
+        self.encode_inputs_and_outputs(&mut bytes);
+        bytes.extend_from_slice(&self.lock_time.to_le_bytes());
+        bytes
+    }
+
+    /// A copy with all witness data removed; its serialization is the txid
+    /// preimage.
+    pub fn without_witness_clone(&self) -> Self {
+        BitcoinTransaction {
+            witnesses: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    fn encode_inputs_and_outputs(&self, bytes: &mut Vec<u8>) {
+        CompactSize::new(self.inputs.len() as u64).encode_into(bytes);
+        for input in &self.inputs {
+            bytes.extend(input.to_bytes());
+        }
+        CompactSize::new(self.outputs.len() as u64).encode_into(bytes);
+        for output in &self.outputs {
+            bytes.extend(output.to_bytes());
+        }
+    }
+
+    fn witness_bytes(&self, input_index: usize) -> Vec<u8> {
+        match self.witnesses.get(input_index) {
+            Some(witness) => witness.to_bytes(),
+            None => Witness::default().to_bytes(),
+        }
+    }
+
+    pub fn txid(&self) -> Txid {
+        Txid(hash::sha256d(&self.to_legacy_bytes()))
+    }
+
+    pub fn sha256(&self) -> [u8; 32] {
+        hash::sha256(&self.to_legacy_bytes())
+    }
+
+    pub fn wtxid(&self) -> Wtxid {
+        Wtxid(hash::sha256d(&self.to_bytes()))
+    }
+
+    pub fn verify_wtxid(&self, expected: &Wtxid) -> bool {
+        self.wtxid() == *expected
+    }
+
+    pub fn verify_txid(&self, expected: &Txid) -> bool {
+        self.txid() == *expected
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.len() < 8 {
-            return Err(BitcoinError::InsufficientBytes);
+        TransactionRef::from_bytes(bytes).map(|(tx, consumed)| (tx.to_owned(), consumed))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    pub fn analyze(bytes: &[u8]) -> Result<TxAnalysis, BitcoinError> {
+        let tx = Self::decode_exact(bytes)?;
+        Ok(TxAnalysis {
+            txid: tx.txid(),
+            wtxid: tx.wtxid(),
+            size: bytes.len(),
+            vsize: tx.vsize(),
+            weight: tx.weight(),
+            input_count: tx.inputs.len(),
+            output_count: tx.outputs.len(),
+            is_segwit: tx.is_segwit(),
+            is_coinbase: tx.is_coinbase(),
+            is_rbf: tx.is_rbf(),
+        })
+    }
+
+    pub fn from_bytes_with_warnings(bytes: &[u8]) -> Result<(Self, Vec<String>), BitcoinError> {
+        let (tx, _) = Self::from_bytes(bytes)?;
+        let mut warnings = Vec::new();
+
+        if tx.version != 1 && tx.version != 2 {
+            warnings.push(format!("non-standard version {}", tx.version));
         }
 
-        let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let (input_count, mut offset) = CompactSize::from_bytes(&bytes[4..])?;
-        offset += 4;
+        for (i, input) in tx.inputs.iter().enumerate() {
+            let has_witness = tx.witnesses.get(i).is_some_and(|stack| !stack.is_empty());
+            if input.script_sig.is_empty() && !has_witness {
+                warnings.push(format!("empty scriptSig on non-segwit input {}", i));
+            }
+        }
 
-        let mut inputs = Vec::new();
-        for _ in 0..input_count.value {
-            let (input, input_bytes) = TransactionInput::from_bytes(&bytes[offset..])?;
-            inputs.push(input);
-            offset += input_bytes;
+        Ok((tx, warnings))
+    }
+
+    pub fn uniform_sequences(&self) -> bool {
+        self.inputs
+            .windows(2)
+            .all(|pair| pair[0].sequence == pair[1].sequence)
+    }
+
+    pub fn version_bytes(&self) -> [u8; 4] {
+        self.version.to_le_bytes()
+    }
+
+    pub fn lock_time_bytes(&self) -> [u8; 4] {
+        self.lock_time.to_le_bytes()
+    }
+
+    /// Input and output counts as they are encoded.
+    pub fn counts(&self) -> (CompactSize, CompactSize) {
+        (
+            CompactSize::new(self.inputs.len() as u64),
+            CompactSize::new(self.outputs.len() as u64),
+        )
+    }
+
+    pub fn skeleton_size(&self) -> usize {
+        let input_count = CompactSize::new(self.inputs.len() as u64).encoded_len();
+        let output_count = CompactSize::new(self.outputs.len() as u64).encoded_len();
+        let outputs: usize = self
+            .outputs
+            .iter()
+            .map(TransactionOutput::serialized_size)
+            .sum();
+        // Each input shrinks to outpoint + a zero-length script + sequence.
+        4 + input_count + self.inputs.len() * (36 + 1 + 4) + output_count + outputs + 4
+    }
+
+    /// Vsize once every input is signed, using estimate_input_weight for the
+    /// type of output each input spends.
+    pub fn estimated_signed_vsize(
+        &self,
+        input_types: &[ScriptType],
+    ) -> Result<usize, BitcoinError> {
+        if input_types.len() != self.inputs.len() {
+            return Err(BitcoinError::invalid(
+                0,
+                "input types do not match input count",
+            ));
+        }
+        let mut weight = (self.skeleton_size() - self.inputs.len() * (36 + 1 + 4)) * 4;
+        let mut legacy_inputs = 0;
+        for &script_type in input_types {
+            match script_type {
+                ScriptType::OpReturn | ScriptType::NonStandard => {
+                    return Err(BitcoinError::invalid(0, "input type cannot be estimated"));
+                }
+                ScriptType::P2pkh => legacy_inputs += 1,
+                _ => {}
+            }
+            weight += estimate_input_weight(script_type);
+        }
+        if legacy_inputs < input_types.len() {
+            // Marker and flag, plus an empty stack for each legacy input.
+            weight += 2 + legacy_inputs;
+        }
+        Ok(weight.div_ceil(4))
+    }
+
+    pub fn cache_key(&self) -> u64 {
+        // FNV-1a, so keys stay the same across runs and toolchains.
+        self.to_bytes()
+            .iter()
+            .fold(0xcbf29ce484222325u64, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    #[cfg(feature = "std")]
+    pub fn opcode_histogram(&self) -> HashMap<u8, usize> {
+        let mut histogram = HashMap::new();
+        for script in self.scripts() {
+            for (opcode, _) in script.raw_instructions().map_while(Result::ok) {
+                *histogram.entry(opcode).or_insert(0) += 1;
+            }
+        }
+        histogram
+    }
+
+    fn scripts(&self) -> impl Iterator<Item = &Script> {
+        let script_sigs = self.inputs.iter().map(|input| &input.script_sig);
+        let script_pubkeys = self.outputs.iter().map(|output| &output.script_pubkey);
+        script_sigs.chain(script_pubkeys)
+    }
+
+    pub fn to_annotated_hex(&self) -> String {
+        let mut lines = vec![format!("{}  # version", hex::encode(self.version_bytes()))];
+        if self.is_segwit() {
+            lines.push(String::from("0001  # segwit marker and flag"));
+        }
+        lines.push(format!(
+            "{}  # input count",
+            hex::encode(CompactSize::new(self.inputs.len() as u64).to_bytes())
+        ));
+        for (i, input) in self.inputs.iter().enumerate() {
+            let script = &input.script_sig;
+            lines.push(format!(
+                "{}  # input {} txid",
+                hex::encode(input.previous_output.txid.0),
+                i
+            ));
+            lines.push(format!(
+                "{}  # input {} vout",
+                hex::encode(input.previous_output.vout.to_le_bytes()),
+                i
+            ));
+            lines.push(format!(
+                "{}  # input {} scriptSig length",
+                hex::encode(CompactSize::new(script.len() as u64).to_bytes()),
+                i
+            ));
+            if !script.is_empty() {
+                lines.push(format!(
+                    "{}  # input {} scriptSig",
+                    hex::encode(&**script),
+                    i
+                ));
+            }
+            lines.push(format!(
+                "{}  # input {} sequence",
+                hex::encode(input.sequence.to_le_bytes()),
+                i
+            ));
+        }
+        lines.push(format!(
+            "{}  # output count",
+            hex::encode(CompactSize::new(self.outputs.len() as u64).to_bytes())
+        ));
+        for (i, output) in self.outputs.iter().enumerate() {
+            let script = &output.script_pubkey;
+            lines.push(format!(
+                "{}  # output {} value",
+                hex::encode(output.value.to_le_bytes()),
+                i
+            ));
+            lines.push(format!(
+                "{}  # output {} scriptPubKey length",
+                hex::encode(CompactSize::new(script.len() as u64).to_bytes()),
+                i
+            ));
+            if !script.is_empty() {
+                lines.push(format!(
+                    "{}  # output {} scriptPubKey",
+                    hex::encode(&**script),
+                    i
+                ));
+            }
+        }
+        if self.is_segwit() {
+            for i in 0..self.inputs.len() {
+                lines.push(format!(
+                    "{}  # input {} witness",
+                    hex::encode(self.witness_bytes(i)),
+                    i
+                ));
+            }
+        }
+        lines.push(format!(
+            "{}  # lock_time",
+            hex::encode(self.lock_time_bytes())
+        ));
+        lines.join("\n")
+    }
+
+    pub fn reorder_inputs(&mut self, new_order: &[usize]) -> Result<(), BitcoinError> {
+        let not_permutation = BitcoinError::invalid(0, "not a permutation of the input indices");
+        if new_order.len() != self.inputs.len() {
+            return Err(not_permutation);
+        }
+        let mut seen = vec![false; self.inputs.len()];
+        for &index in new_order {
+            match seen.get_mut(index) {
+                Some(slot) if !*slot => *slot = true,
+                _ => return Err(not_permutation),
+            }
+        }
+        self.inputs = new_order
+            .iter()
+            .map(|&index| self.inputs[index].clone())
+            .collect();
+        // Witnesses are matched to inputs by position, so they move too.
+        if !self.witnesses.is_empty() {
+            self.witnesses = new_order
+                .iter()
+                .map(|&index| self.witnesses.get(index).cloned().unwrap_or_default())
+                .collect();
+        }
+        Ok(())
+    }
+
+    pub fn prevouts_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.inputs.len() * 36);
+        for input in &self.inputs {
+            bytes.extend(input.previous_output.to_bytes());
+        }
+        bytes
+    }
+
+    pub fn sequences_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.inputs.len() * 4);
+        for input in &self.inputs {
+            bytes.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Serialized outputs back to back; the BIP-143 hashOutputs preimage.
+    pub fn outputs_bytes(&self) -> Vec<u8> {
+        let size = self.outputs.iter().map(TransactionOutput::serialized_size);
+        let mut bytes = Vec::with_capacity(size.sum());
+        for output in &self.outputs {
+            bytes.extend(output.to_bytes());
+        }
+        bytes
+    }
+
+    pub fn all_data_pushes(&self) -> Vec<Vec<u8>> {
+        self.scripts()
+            .flat_map(|script| script.raw_instructions().map_while(Result::ok))
+            .filter_map(|(_, data)| data.map(<[u8]>::to_vec))
+            .chain(self.witnesses.iter().flat_map(|w| w.items.iter().cloned()))
+            .collect()
+    }
+
+    pub fn verify_internal_consistency(bytes: &[u8]) -> Result<(), BitcoinError> {
+        Self::decode_exact(bytes).map(|_| ())
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let segwit = self.is_segwit();
+        let marker = segwit.then(|| vec![0x00, 0x01]);
+        let witness_count = if segwit { self.inputs.len() } else { 0 };
+        let witnesses = (0..witness_count).map(|i| self.witness_bytes(i));
+        core::iter::once(self.version_bytes().to_vec())
+            .chain(marker)
+            .chain(core::iter::once(
+                CompactSize::new(self.inputs.len() as u64).to_bytes(),
+            ))
+            .chain(self.inputs.iter().map(TransactionInput::to_bytes))
+            .chain(core::iter::once(
+                CompactSize::new(self.outputs.len() as u64).to_bytes(),
+            ))
+            .chain(self.outputs.iter().map(TransactionOutput::to_bytes))
+            .chain(witnesses)
+            .chain(core::iter::once(self.lock_time_bytes().to_vec()))
+    }
+
+    pub fn base_size(&self) -> usize {
+        let scripts: usize = self
+            .inputs
+            .iter()
+            .map(|input| input.script_sig.serialized_size())
+            .sum();
+        self.skeleton_size() - self.inputs.len() + scripts
+    }
+
+    pub fn serialized_size(&self) -> usize {
+        if !self.is_segwit() {
+            return self.base_size();
         }
+        // Inputs past the end of `witnesses` serialize as an empty stack.
+        let witnesses: usize = (0..self.inputs.len())
+            .map(|i| self.witnesses.get(i).map_or(1, Witness::serialized_size))
+            .sum();
+        self.base_size() + 2 + witnesses
+    }
+
+    /// Serialized size if the last output, assumed to be change, were dropped.
+    pub fn size_without_change(&self) -> usize {
+        let Some(change) = self.outputs.last() else {
+            return self.serialized_size();
+        };
+        let count = |n: usize| CompactSize::new(n as u64).encoded_len();
+        let n = self.outputs.len();
+        self.serialized_size() - change.serialized_size() - count(n) + count(n - 1)
+    }
+
+    /// BIP-141 total size: the serialization including any witness data.
+    pub fn total_size(&self) -> usize {
+        self.serialized_size()
+    }
 
-        if bytes.len() < offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
+    pub fn weight(&self) -> usize {
+        self.base_size() * 3 + self.total_size()
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(4)
+    }
+
+    /// Virtual size as mempool policy charges it: the larger of the weight
+    /// and 20 bytes per unit of sigop cost, in vbytes. `prevout_scripts` are
+    /// the scriptPubKeys being spent, in input order; they select which P2SH
+    /// redeem scripts and witness scripts are counted.
+    pub fn adjusted_vsize(&self, prevout_scripts: &[Script]) -> Result<usize, BitcoinError> {
+        const BYTES_PER_SIGOP: usize = 20;
+        const WITNESS_SCALE: usize = 4;
+        if prevout_scripts.len() != self.inputs.len() {
+            return Err(BitcoinError::invalid(
+                0,
+                "prevout data does not match input count",
+            ));
+        }
+        let legacy: usize = self
+            .inputs
+            .iter()
+            .map(|input| input.script_sig.sigop_count(false))
+            .chain(
+                self.outputs
+                    .iter()
+                    .map(|output| output.script_pubkey.sigop_count(false)),
+            )
+            .sum();
+        let mut cost = legacy * WITNESS_SCALE;
+        for (i, (input, prevout)) in self.inputs.iter().zip(prevout_scripts).enumerate() {
+            let mut program = prevout.clone();
+            if prevout.classify() == ScriptType::P2sh {
+                // Consensus only evaluates a redeem script from a push-only scriptSig.
+                if !input.script_sig.is_push_only() {
+                    continue;
+                }
+                let redeem_script = input
+                    .script_sig
+                    .raw_instructions()
+                    .map_while(Result::ok)
+                    .last();
+                let Some((_, Some(redeem_script))) = redeem_script else {
+                    continue;
+                };
+                program = Script::new(redeem_script.to_vec());
+                cost += program.sigop_count(true) * WITNESS_SCALE;
+            }
+            // Witness sigops are not scaled.
+            cost += match program.classify() {
+                ScriptType::P2wpkh => 1,
+                ScriptType::P2wsh => self
+                    .witnesses
+                    .get(i)
+                    .and_then(|witness| witness.last())
+                    .map_or(0, |script| Script::new(script.clone()).sigop_count(true)),
+                _ => 0,
+            };
+        }
+        Ok(self
+            .weight()
+            .max(cost * BYTES_PER_SIGOP)
+            .div_ceil(WITNESS_SCALE))
+    }
+
+    /// Compares fee rates per vbyte exactly, by cross-multiplying instead of
+    /// dividing.
+    pub fn fee_rate_cmp(&self, self_fee: u64, other: &Self, other_fee: u64) -> Ordering {
+        let lhs = self_fee as u128 * other.vsize() as u128;
+        let rhs = other_fee as u128 * self.vsize() as u128;
+        lhs.cmp(&rhs)
+    }
+
+    pub fn vsize_delta(&self, other: &Self) -> i64 {
+        self.vsize() as i64 - other.vsize() as i64
+    }
+
+    /// `spent` holds the outputs consumed by each input, in input order.
+    pub fn fee(&self, spent: &[TransactionOutput]) -> Result<u64, BitcoinError> {
+        if spent.len() != self.inputs.len() {
+            return Err(BitcoinError::invalid(
+                0,
+                "spent output count does not match input count",
+            ));
+        }
+        self.fee_from_values(spent.iter().map(|output| output.value), 0)
+    }
+
+    /// The fee if `new_output` were appended, leaving `self` unchanged.
+    pub fn fee_if_output_added(
+        &self,
+        prevout_values: &[u64],
+        new_output: &TransactionOutput,
+    ) -> Result<u64, BitcoinError> {
+        if prevout_values.len() != self.inputs.len() {
+            return Err(BitcoinError::invalid(
+                0,
+                "spent output count does not match input count",
+            ));
+        }
+        self.fee_from_values(prevout_values.iter().copied(), new_output.value)
+    }
+
+    fn fee_from_values(
+        &self,
+        prevout_values: impl Iterator<Item = u64>,
+        extra_output_value: u64,
+    ) -> Result<u64, BitcoinError> {
+        let input_value = checked_sum(prevout_values)?;
+        let output_value = checked_sum(
+            self.outputs
+                .iter()
+                .map(|output| output.value)
+                .chain(core::iter::once(extra_output_value)),
+        )?;
+        input_value
+            .checked_sub(output_value)
+            .ok_or(BitcoinError::invalid(0, "outputs exceed inputs"))
+    }
+
+    /// Bytes consumed by from_bytes and the length of the re-serialization.
+    /// Decoding rejects non-minimal CompactSize prefixes with
+    /// NonCanonicalVarInt, so a successful result always has equal sizes.
+    pub fn parse_and_compare_size(bytes: &[u8]) -> Result<(usize, usize), BitcoinError> {
+        let (tx, consumed) = Self::from_bytes(bytes)?;
+        Ok((consumed, tx.to_bytes().len()))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn inputs_by_txid(&self) -> HashMap<Txid, Vec<usize>> {
+        let mut groups: HashMap<Txid, Vec<usize>> = HashMap::new();
+        for (i, input) in self.inputs.iter().enumerate() {
+            groups
+                .entry(input.previous_output.txid.clone())
+                .or_default()
+                .push(i);
+        }
+        groups
+    }
+
+    pub fn is_consolidation(&self) -> bool {
+        self.outputs.len() == 1 && self.inputs.len() >= 3 * self.outputs.len()
+    }
+
+    pub fn prevout_script_for_input(
+        &self,
+        input_index: usize,
+        prev_tx: &BitcoinTransaction,
+    ) -> Option<Script> {
+        let outpoint = &self.inputs.get(input_index)?.previous_output;
+        if outpoint.txid != prev_tx.txid() {
+            return None;
+        }
+        prev_tx
+            .outputs
+            .get(outpoint.vout as usize)
+            .map(|output| output.script_pubkey.clone())
+    }
+
+    pub fn from_hex(s: &str) -> Result<Self, BitcoinError> {
+        let bytes = hex::decode(s).map_err(|_| BitcoinError::invalid(0, "invalid hex"))?;
+        Self::decode_exact(&bytes)
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    pub fn equal_value_output_count(&self) -> usize {
+        let mut counts: BTreeMap<u64, usize> = BTreeMap::new();
+        for output in &self.outputs {
+            *counts.entry(output.value).or_default() += 1;
+        }
+        counts.into_values().max().unwrap_or(0)
+    }
+
+    pub fn witness_weight(&self) -> usize {
+        self.serialized_size() - self.base_size()
+    }
+
+    pub fn witness_weight_fraction(&self) -> f64 {
+        self.witness_weight() as f64 / self.weight() as f64
+    }
+
+    /// Splits `fee` into the shares paid for base and witness data, in
+    /// proportion to their weight.
+    pub fn fee_breakdown(&self, fee: u64) -> (f64, f64) {
+        let weight = self.weight() as f64;
+        let base = fee as f64 * (self.base_size() * 4) as f64 / weight;
+        let witness = fee as f64 * self.witness_weight() as f64 / weight;
+        (base, witness)
+    }
+
+    pub fn build_unsigned(
+        inputs: Vec<OutPoint>,
+        outputs: Vec<(u64, Script)>,
+        lock_time: u32,
+    ) -> BitcoinTransaction {
+        let inputs = inputs
+            .into_iter()
+            .map(|outpoint| TransactionInput::new(outpoint, Script::default(), 0xFFFFFFFD))
+            .collect();
+        let outputs = outputs
+            .into_iter()
+            .map(|(value, script_pubkey)| TransactionOutput::new(value, script_pubkey))
+            .collect();
+        BitcoinTransaction::with_outputs(2, inputs, outputs, lock_time)
+    }
+
+    /// True if any input signals BIP-125 replaceability.
+    pub fn is_rbf(&self) -> bool {
+        self.inputs.iter().any(|input| input.sequence < 0xFFFFFFFE)
+    }
+
+    pub fn all_inputs_rbf(&self) -> bool {
+        !self.inputs.is_empty() && self.inputs.iter().all(|input| input.sequence < 0xFFFFFFFE)
+    }
+
+    pub fn all_outputs_standard(&self) -> bool {
+        self.outputs
+            .iter()
+            .all(|output| output.script_pubkey.is_standard())
+    }
+
+    pub fn distinct_output_types(&self) -> usize {
+        let mut seen = Vec::new();
+        for output in &self.outputs {
+            let script_type = output.script_pubkey.classify();
+            if !seen.contains(&script_type) {
+                seen.push(script_type);
+            }
+        }
+        seen.len()
+    }
+
+    pub fn dust_output_indices(&self, dust_relay_fee: u64) -> Vec<usize> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.is_dust(dust_relay_fee))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Zero-value outputs other than OP_RETURN; policy treats these as dust.
+    pub fn suspicious_zero_outputs(&self) -> Vec<usize> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.value == 0 && output.script_pubkey.first() != Some(&0x6A))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn segwit_savings_estimate(&self) -> usize {
+        let mut converted = self.clone();
+        converted
+            .witnesses
+            .resize(self.inputs.len(), Witness::default());
+        for (input, witness) in converted.inputs.iter_mut().zip(&mut converted.witnesses) {
+            // A p2pkh scriptSig is <sig> <pubkey>; as p2wpkh the same two
+            // items move to the witness.
+            let pushes: Option<Vec<_>> = input
+                .script_sig
+                .raw_instructions()
+                .map(|op| op.ok().and_then(|(_, data)| data))
+                .collect();
+            let Some(pushes) = pushes else { continue };
+            let is_p2pkh_spend = match pushes.as_slice() {
+                [sig, pubkey] => (9..=73).contains(&sig.len()) && matches!(pubkey.len(), 33 | 65),
+                _ => false,
+            };
+            if is_p2pkh_spend && witness.is_empty() {
+                *witness = Witness::new(pushes.iter().map(|data| data.to_vec()).collect());
+                input.script_sig = Script::default();
+            }
         }
-        let lock_time = u32::from_le_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]);
-        Ok((
-            BitcoinTransaction::new(version, inputs, lock_time),
-            offset + 4,
+        self.vsize().saturating_sub(converted.vsize())
+    }
+
+    pub fn batch_inputs(&self, max_inputs: usize) -> Vec<Vec<TransactionInput>> {
+        self.inputs
+            .chunks(max_inputs.max(1))
+            .map(<[TransactionInput]>::to_vec)
+            .collect()
+    }
+
+    pub fn with_sequence(&self, input_index: usize, sequence: u32) -> Result<Self, BitcoinError> {
+        let mut tx = self.clone();
+        let input = tx
+            .inputs
+            .get_mut(input_index)
+            .ok_or(BitcoinError::invalid(0, "input index out of range"))?;
+        input.sequence = sequence;
+        Ok(tx)
+    }
+
+    pub fn is_simple_payment(&self) -> bool {
+        self.outputs.len() == 2
+    }
+
+    pub fn find_output(&self, script: &Script, value: u64) -> Option<usize> {
+        self.outputs
+            .iter()
+            .position(|output| output.value == value && output.script_pubkey == *script)
+    }
+
+    /// Index of an output paying to one of `my_scripts`, which a CPFP child
+    /// could spend.
+    pub fn has_spendable_change_for(&self, my_scripts: &[Script]) -> Option<usize> {
+        self.outputs
+            .iter()
+            .position(|output| my_scripts.contains(&output.script_pubkey))
+    }
+
+    /// Outputs with their original indices, largest value first. Equal values
+    /// keep their original order.
+    pub fn outputs_by_value_desc(&self) -> Vec<(usize, &TransactionOutput)> {
+        let mut outputs: Vec<_> = self.outputs.iter().enumerate().collect();
+        outputs.sort_by_key(|(_, output)| core::cmp::Reverse(output.value));
+        outputs
+    }
+
+    pub fn has_output_address_reuse(&self) -> bool {
+        self.outputs.iter().enumerate().any(|(i, output)| {
+            self.outputs[i + 1..]
+                .iter()
+                .any(|other| other.script_pubkey == output.script_pubkey)
+        })
+    }
+
+    pub fn summary_line(&self) -> String {
+        format!(
+            "txid={} in={} out={} vsize={} rbf={}",
+            &self.txid().to_rpc_hex()[..8],
+            self.inputs.len(),
+            self.outputs.len(),
+            self.vsize(),
+            if self.is_rbf() { "yes" } else { "no" }
+        )
+    }
+
+    pub fn is_pinning_risk(&self, fee: u64, min_rate: f64) -> bool {
+        // Large enough that replacing it under BIP-125 rule 3 gets expensive.
+        const PINNING_VSIZE: usize = 10_000;
+        let vsize = self.vsize();
+        vsize >= PINNING_VSIZE && (fee as f64 / vsize as f64) < min_rate
+    }
+
+    pub fn ambiguous_change(&self) -> bool {
+        let [a, b] = self.outputs.as_slice() else {
+            return false;
+        };
+        let same_type = a.script_pubkey.classify() == b.script_pubkey.classify();
+        let is_round = |value: u64| value.is_multiple_of(100_000);
+        same_type && is_round(a.value) == is_round(b.value)
+    }
+
+    pub fn to_p2p_message(&self, network_magic: [u8; 4]) -> Vec<u8> {
+        message::frame(network_magic, "tx", &self.to_bytes())
+    }
+
+    pub fn weighted_size(&self, witness_discount: u32) -> usize {
+        self.base_size() * witness_discount as usize + self.witness_weight()
+    }
+
+    pub fn empty_scriptsig_inputs(&self) -> Vec<usize> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| input.script_sig.is_empty())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn finalize(&self) -> (Vec<u8>, Txid) {
+        let bytes = self.to_bytes();
+        // Only segwit transactions need a separate legacy serialization.
+        let txid = if self.is_segwit() {
+            self.txid()
+        } else {
+            Txid(hash::sha256d(&bytes))
+        };
+        (bytes, txid)
+    }
+
+    pub fn spends_coinbase_at(
+        &self,
+        prevout_is_coinbase: &[bool],
+        prevout_heights: &[u32],
+        current_height: u32,
+    ) -> Result<bool, BitcoinError> {
+        const COINBASE_MATURITY: u32 = 100;
+        let n = self.inputs.len();
+        if prevout_is_coinbase.len() != n || prevout_heights.len() != n {
+            return Err(BitcoinError::invalid(
+                0,
+                "prevout data does not match input count",
+            ));
+        }
+        Ok(prevout_is_coinbase
+            .iter()
+            .zip(prevout_heights)
+            .any(|(&coinbase, &height)| {
+                coinbase && current_height < height.saturating_add(COINBASE_MATURITY)
+            }))
+    }
+
+    /// Checks that a coinbase at `height` pays out no more than the block
+    /// subsidy plus `total_fees`.
+    pub fn validate_coinbase_value(
+        &self,
+        height: u32,
+        total_fees: u64,
+    ) -> Result<(), BitcoinError> {
+        let allowed = block_subsidy(height)
+            .checked_add(total_fees)
+            .ok_or(BitcoinError::invalid(0, "value overflow"))?;
+        let paid = checked_sum(self.outputs.iter().map(|output| output.value))?;
+        if paid > allowed {
+            return Err(BitcoinError::invalid(
+                0,
+                "coinbase pays more than subsidy plus fees",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn content_fingerprint(&self) -> [u8; 32] {
+        let mut inputs: Vec<_> = self.inputs.iter().map(TransactionInput::to_bytes).collect();
+        let mut outputs: Vec<_> = self
+            .outputs
+            .iter()
+            .map(TransactionOutput::to_bytes)
+            .collect();
+        inputs.sort();
+        outputs.sort();
+
+        let mut preimage = self.version_bytes().to_vec();
+        CompactSize::new(inputs.len() as u64).encode_into(&mut preimage);
+        preimage.extend(inputs.concat());
+        CompactSize::new(outputs.len() as u64).encode_into(&mut preimage);
+        preimage.extend(outputs.concat());
+        preimage.extend_from_slice(&self.lock_time_bytes());
+        hash::sha256(&preimage)
+    }
+
+    /// A single input spending the null outpoint.
+    pub fn is_coinbase(&self) -> bool {
+        match self.inputs.as_slice() {
+            [input] => {
+                input.previous_output.txid.0 == [0; 32] && input.previous_output.vout == u32::MAX
+            }
+            _ => false,
+        }
+    }
+
+    pub fn locktime_is_effective(&self) -> bool {
+        self.lock_time != 0 && self.inputs.iter().any(|input| input.sequence != 0xFFFFFFFF)
+    }
+
+    pub fn witness_to_scriptsig_stack(&self, input_index: usize) -> Vec<Vec<u8>> {
+        self.witnesses
+            .get(input_index)
+            .map(|witness| witness.items.clone())
+            .unwrap_or_default()
+    }
+
+    /// Rejects witness stacks with an item above the 520-byte standardness
+    /// limit on stack elements.
+    pub fn validate_witness_items(&self) -> Result<(), BitcoinError> {
+        const MAX_ITEM_SIZE: usize = 520;
+        let oversized = self
+            .witnesses
+            .iter()
+            .flat_map(|witness| witness.iter())
+            .any(|item| item.len() > MAX_ITEM_SIZE);
+        if oversized {
+            return Err(BitcoinError::invalid(0, "witness item exceeds 520 bytes"));
+        }
+        Ok(())
+    }
+
+    /// True for a P2SH-wrapped segwit spend: the scriptSig is a single push of
+    /// a v0 witness program and the input has a non-empty witness.
+    pub fn is_nested_segwit(&self, input_index: usize) -> bool {
+        let Some(input) = self.inputs.get(input_index) else {
+            return false;
+        };
+        let has_witness = self
+            .witnesses
+            .get(input_index)
+            .is_some_and(|witness| !witness.is_empty());
+        let mut pushes = input.script_sig.raw_instructions();
+        let program = match (pushes.next(), pushes.next()) {
+            (Some(Ok((_, Some(data)))), None) => Script::new(data.to_vec()),
+            _ => return false,
+        };
+        has_witness && matches!(program.classify(), ScriptType::P2wpkh | ScriptType::P2wsh)
+    }
+
+    pub fn looks_like_coinjoin(&self, min_participants: usize) -> bool {
+        self.inputs.len() >= min_participants && self.equal_value_output_count() >= min_participants
+    }
+
+    pub fn graph_edges(&self) -> Vec<(Txid, u32)> {
+        self.inputs
+            .iter()
+            .map(|input| {
+                let outpoint = &input.previous_output;
+                (outpoint.txid.clone(), outpoint.vout)
+            })
+            .collect()
+    }
+
+    /// True if any input spends an output of `parent`.
+    pub fn is_child_of(&self, parent: &BitcoinTransaction) -> bool {
+        let parent_txid = parent.txid();
+        self.inputs
+            .iter()
+            .any(|input| input.previous_output.txid == parent_txid)
+    }
+
+    /// Output indices of `parent` spent by this transaction, in input order.
+    pub fn spent_output_indices_of(&self, parent: &BitcoinTransaction) -> Vec<u32> {
+        let parent_txid = parent.txid();
+        self.inputs
+            .iter()
+            .filter(|input| input.previous_output.txid == parent_txid)
+            .map(|input| input.previous_output.vout)
+            .collect()
+    }
+}
+
+// Everything BitcoinTransaction::analyze derives from a serialized transaction.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TxAnalysis {
+    pub txid: Txid,
+    pub wtxid: Wtxid,
+    pub size: usize,
+    pub vsize: usize,
+    pub weight: usize,
+    pub input_count: usize,
+    pub output_count: usize,
+    pub is_segwit: bool,
+    pub is_coinbase: bool,
+    pub is_rbf: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    version: u32,
+    inputs: Vec<TransactionInput>,
+    outputs: Vec<TransactionOutput>,
+    lock_time: u32,
+}
+
+impl TransactionBuilder {
+    pub fn new(version: u32) -> Self {
+        TransactionBuilder {
+            version,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            lock_time: 0,
+        }
+    }
+
+    pub fn add_input(
+        mut self,
+        previous_output: OutPoint,
+        script_sig: Script,
+        sequence: u32,
+    ) -> Self {
+        self.inputs
+            .push(TransactionInput::new(previous_output, script_sig, sequence));
+        self
+    }
+
+    pub fn add_input_default_sequence(self, previous_output: OutPoint, script_sig: Script) -> Self {
+        self.add_input(previous_output, script_sig, 0xFFFFFFFF)
+    }
+
+    pub fn add_output(mut self, value: u64, script_pubkey: Script) -> Self {
+        self.outputs
+            .push(TransactionOutput::new(value, script_pubkey));
+        self
+    }
+
+    pub fn lock_time(mut self, lock_time: u32) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn build(self) -> Result<BitcoinTransaction, BitcoinError> {
+        const MAX_MONEY: u64 = 21_000_000 * 100_000_000;
+        const MAX_SCRIPT_SIZE: usize = 10_000;
+
+        if self.inputs.is_empty() {
+            return Err(BitcoinError::invalid(0, "transaction has no inputs"));
+        }
+        if self.outputs.is_empty() {
+            return Err(BitcoinError::invalid(0, "transaction has no outputs"));
+        }
+        let mut total: u64 = 0;
+        for output in &self.outputs {
+            total = total.saturating_add(output.value);
+            if output.value > MAX_MONEY || total > MAX_MONEY {
+                return Err(BitcoinError::invalid(0, "output value out of range"));
+            }
+        }
+        let oversized = self
+            .inputs
+            .iter()
+            .map(|input| &input.script_sig)
+            .chain(self.outputs.iter().map(|output| &output.script_pubkey))
+            .any(|script| script.len() > MAX_SCRIPT_SIZE);
+        if oversized {
+            return Err(BitcoinError::invalid(0, "script exceeds size limit"));
+        }
+
+        Ok(BitcoinTransaction::with_outputs(
+            self.version,
+            self.inputs,
+            self.outputs,
+            self.lock_time,
         ))
     }
 }
 
+fn checked_sum(mut values: impl Iterator<Item = u64>) -> Result<u64, BitcoinError> {
+    values
+        .try_fold(0u64, u64::checked_add)
+        .ok_or(BitcoinError::invalid(0, "value overflow"))
+}
+
+pub fn rbf_min_replacement_fee(
+    original_fee: u64,
+    original_vsize: usize,
+    replacement_vsize: usize,
+    relay_rate: u64,
+) -> u64 {
+    // Rules 3 and 4: cover the original fee plus relay of the replacement itself.
+    let absolute =
+        original_fee.saturating_add((replacement_vsize as u64).saturating_mul(relay_rate));
+    // The replacement must also pay a strictly higher fee rate than the original.
+    let by_rate = if original_vsize == 0 {
+        0
+    } else {
+        let scaled = original_fee as u128 * replacement_vsize as u128 / original_vsize as u128;
+        u64::try_from(scaled + 1).unwrap_or(u64::MAX)
+    };
+    absolute.max(by_rate)
+}
+
+/// Unsigned one-input, one-output transaction paying `prevout_value - fee`.
+pub fn simple_spend(
+    prevout: OutPoint,
+    prevout_value: u64,
+    dest_script: Script,
+    fee: u64,
+) -> Result<BitcoinTransaction, BitcoinError> {
+    let value = prevout_value
+        .checked_sub(fee)
+        .ok_or(BitcoinError::invalid(0, "fee exceeds prevout value"))?;
+    Ok(BitcoinTransaction::build_unsigned(
+        vec![prevout],
+        vec![(value, dest_script)],
+        0,
+    ))
+}
+
+/// A zero-value OP_RETURN output carrying up to 80 bytes of data.
+pub fn op_return_output(data: &[u8]) -> Result<TransactionOutput, BitcoinError> {
+    let mut script = vec![0x6A];
+    match data.len() {
+        0..=75 => script.push(data.len() as u8),
+        76..=80 => script.extend_from_slice(&[0x4C, data.len() as u8]),
+        _ => return Err(BitcoinError::invalid(0, "OP_RETURN data exceeds 80 bytes")),
+    }
+    script.extend_from_slice(data);
+    Ok(TransactionOutput::new(0, Script::new(script)))
+}
+
+/// Typical weight of a signed input spending `script_type`, counting the
+/// outpoint, scriptSig, sequence and witness. Unspendable and nonstandard
+/// outputs have no estimate and return 0.
+pub fn estimate_input_weight(script_type: ScriptType) -> usize {
+    // Outpoint, scriptSig length and sequence, at 4 WU per byte.
+    const BASE: usize = (36 + 1 + 4) * 4;
+    // Item count, 72-byte signature and 33-byte compressed key.
+    const P2WPKH_WITNESS: usize = 1 + (1 + 72) + (1 + 33);
+    match script_type {
+        // <sig> <pubkey> scriptSig.
+        ScriptType::P2pkh => BASE + (1 + 72 + 1 + 33) * 4,
+        // Assumed to wrap p2wpkh: the scriptSig pushes the 22-byte program.
+        ScriptType::P2sh => BASE + 23 * 4 + P2WPKH_WITNESS,
+        ScriptType::P2wpkh => BASE + P2WPKH_WITNESS,
+        // Assumed 2-of-3 multisig: dummy, two signatures and the 105-byte script.
+        ScriptType::P2wsh => BASE + 1 + 1 + 2 * (1 + 72) + (1 + 105),
+        // Key path spend with a 64-byte Schnorr signature.
+        ScriptType::P2tr => BASE + 1 + (1 + 64),
+        ScriptType::OpReturn | ScriptType::NonStandard => 0,
+    }
+}
+
+/// Vsize of the smallest signed transaction spending one output of
+/// `script_type` to a single p2wpkh output. Zero for unspendable types.
+pub fn minimal_spend_size(script_type: ScriptType) -> usize {
+    let mut p2wpkh = vec![0x00, 0x14];
+    p2wpkh.extend([0; 20]);
+    let tx = BitcoinTransaction::build_unsigned(
+        vec![OutPoint::new([0; 32], 0)],
+        vec![(0, Script::new(p2wpkh))],
+        0,
+    );
+    tx.estimated_signed_vsize(&[script_type]).unwrap_or(0)
+}
+
+pub fn block_subsidy(height: u32) -> u64 {
+    let halvings = height / 210_000;
+    if halvings >= 64 {
+        return 0;
+    }
+    50_0000_0000 >> halvings
+}
+
+pub fn package_vsize(txs: &[BitcoinTransaction]) -> usize {
+    txs.iter().map(BitcoinTransaction::vsize).sum()
+}
+
+pub fn package_fee_rate(txs: &[BitcoinTransaction], fees: &[u64]) -> Result<f64, BitcoinError> {
+    if txs.len() != fees.len() {
+        return Err(BitcoinError::invalid(
+            0,
+            "fee count does not match transaction count",
+        ));
+    }
+    let vsize = package_vsize(txs);
+    if vsize == 0 {
+        return Err(BitcoinError::invalid(0, "empty package"));
+    }
+    let total_fee = checked_sum(fees.iter().copied())?;
+    Ok(total_fee as f64 / vsize as f64)
+}
+
+pub fn parse_p2p_tx_message(
+    bytes: &[u8],
+    expected_magic: [u8; 4],
+) -> Result<BitcoinTransaction, BitcoinError> {
+    let message = NetworkMessage::decode_exact(bytes)?;
+    if message.magic != expected_magic {
+        return Err(BitcoinError::invalid(0, "unexpected network magic"));
+    }
+    match message.payload {
+        MessagePayload::Tx(tx) => Ok(tx),
+        _ => Err(BitcoinError::invalid(4, "not a tx message")),
+    }
+}
+
+pub fn concat_transactions(txs: &[BitcoinTransaction]) -> Vec<u8> {
+    txs.iter().flat_map(BitcoinTransaction::to_bytes).collect()
+}
+
+pub fn split_transactions(
+    bytes: &[u8],
+    count: usize,
+) -> Result<Vec<BitcoinTransaction>, BitcoinError> {
+    let mut txs = Vec::new();
+    let mut offset = 0;
+    for _ in 0..count {
+        let (tx, consumed) =
+            BitcoinTransaction::from_bytes(&bytes[offset..]).map_err(|e| e.at(offset))?;
+        txs.push(tx);
+        offset += consumed;
+    }
+    if offset != bytes.len() {
+        return Err(BitcoinError::invalid(offset, "trailing bytes"));
+    }
+    Ok(txs)
+}
+
 impl fmt::Display for BitcoinTransaction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Bitcoin Transaction:\n")?;
-        write!(f, "  Version: {}\n", self.version)?;
-        write!(f, "  Inputs:\n")?;
+        writeln!(f, "Bitcoin Transaction:")?;
+        writeln!(f, "  Version: {}", self.version)?;
+        writeln!(f, "  Inputs:")?;
         for (i, input) in self.inputs.iter().enumerate() {
-            write!(f, "    Input {}:\n", i + 1)?;
-            write!(
+            writeln!(f, "    Input {}:", i + 1)?;
+            writeln!(
                 f,
-                "      Previous Output Vout: {}\n",
+                "      Previous Output Vout: {}",
                 input.previous_output.vout
             )?;
-            write!(
+            writeln!(
                 f,
-                "      ScriptSig: length={}, bytes={}\n",
+                "      ScriptSig: length={}, bytes={}",
                 input.script_sig.len(),
                 hex::encode(&*input.script_sig)
             )?;
-            write!(f, "      Sequence: {}\n", input.sequence)?;
+            writeln!(f, "      Sequence: {}", input.sequence)?;
+            if let Some(stack) = self.witnesses.get(i) {
+                writeln!(f, "      Witness: {} items", stack.len())?;
+            }
+        }
+        writeln!(f, "  Outputs:")?;
+        for (i, output) in self.outputs.iter().enumerate() {
+            writeln!(f, "    Output {}:", i + 1)?;
+            writeln!(f, "      Value: {}", output.value)?;
+            writeln!(
+                f,
+                "      ScriptPubKey: length={}, bytes={}",
+                output.script_pubkey.len(),
+                hex::encode(&*output.script_pubkey)
+            )?;
         }
-        write!(f, "  Lock Time: {}\n", self.lock_time)
+        writeln!(f, "  Lock Time: {}", self.lock_time)
     }
 }
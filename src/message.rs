@@ -0,0 +1,291 @@
+use crate::hash;
+use crate::{
+    BitcoinError, BitcoinTransaction, CompactSize, decode_exact, read_count_checked, require,
+};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NetAddress {
+    pub services: u64,
+    // IPv6, or IPv4-mapped IPv6 (::ffff:a.b.c.d).
+    pub ip: [u8; 16],
+    pub port: u16,
+}
+
+impl NetAddress {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.services.to_le_bytes());
+        out.extend_from_slice(&self.ip);
+        // The only big-endian field in the protocol.
+        out.extend_from_slice(&self.port.to_be_bytes());
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        require(bytes, "network address", 0, 26)?;
+        let mut ip = [0u8; 16];
+        ip.copy_from_slice(&bytes[8..24]);
+        let address = NetAddress {
+            services: u64_at(bytes, 0),
+            ip,
+            port: u16::from_be_bytes([bytes[24], bytes[25]]),
+        };
+        Ok((address, 26))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct VersionMessage {
+    pub version: u32,
+    pub services: u64,
+    pub timestamp: i64,
+    pub receiver: NetAddress,
+    pub sender: NetAddress,
+    pub nonce: u64,
+    pub user_agent: String,
+    pub start_height: i32,
+    // Peers older than BIP-37 omit this; they are treated as relaying.
+    pub relay: bool,
+}
+
+impl VersionMessage {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.version.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.services.to_le_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        self.receiver.encode_into(&mut bytes);
+        self.sender.encode_into(&mut bytes);
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        CompactSize::new(self.user_agent.len() as u64).encode_into(&mut bytes);
+        bytes.extend_from_slice(self.user_agent.as_bytes());
+        bytes.extend_from_slice(&self.start_height.to_le_bytes());
+        bytes.push(self.relay as u8);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        require(bytes, "version header", 0, 20)?;
+        let (receiver, _) = NetAddress::from_bytes(&bytes[20..]).map_err(|e| e.at(20))?;
+        let (sender, _) = NetAddress::from_bytes(&bytes[46..]).map_err(|e| e.at(46))?;
+        require(bytes, "nonce", 72, 8)?;
+
+        let (len, len_bytes) = CompactSize::from_bytes(&bytes[80..]).map_err(|e| e.at(80))?;
+        let mut offset = 80 + len_bytes;
+        let len = usize::try_from(len.value)
+            .map_err(|_| BitcoinError::invalid(80, "user agent length does not fit in usize"))?;
+        require(bytes, "user agent", offset, len)?;
+        let user_agent = String::from_utf8(bytes[offset..offset + len].to_vec())
+            .map_err(|_| BitcoinError::invalid(offset, "user agent is not UTF-8"))?;
+        offset += len;
+
+        require(bytes, "start height", offset, 4)?;
+        let start_height = u32_at(bytes, offset) as i32;
+        offset += 4;
+        let relay = match bytes.get(offset) {
+            Some(&flag) => {
+                offset += 1;
+                flag != 0
+            }
+            None => true,
+        };
+
+        let message = VersionMessage {
+            version: u32_at(bytes, 0),
+            services: u64_at(bytes, 4),
+            timestamp: u64_at(bytes, 12) as i64,
+            receiver,
+            sender,
+            nonce: u64_at(bytes, 72),
+            user_agent,
+            start_height,
+            relay,
+        };
+        Ok((message, offset))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Inventory {
+    pub inv_type: u32,
+    pub hash: [u8; 32],
+}
+
+impl Inventory {
+    pub const ERROR: u32 = 0;
+    pub const TX: u32 = 1;
+    pub const BLOCK: u32 = 2;
+    pub const WITNESS_TX: u32 = 0x40000001;
+    pub const WITNESS_BLOCK: u32 = 0x40000002;
+
+    pub fn new(inv_type: u32, hash: [u8; 32]) -> Self {
+        Inventory { inv_type, hash }
+    }
+}
+
+fn inventory_to_bytes(items: &[Inventory]) -> Vec<u8> {
+    let mut bytes = CompactSize::new(items.len() as u64).to_bytes();
+    for item in items {
+        bytes.extend_from_slice(&item.inv_type.to_le_bytes());
+        bytes.extend_from_slice(&item.hash);
+    }
+    bytes
+}
+
+fn inventory_from_bytes(bytes: &[u8]) -> Result<(Vec<Inventory>, usize), BitcoinError> {
+    let (count, mut offset) = read_count_checked(bytes, 36)?;
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&bytes[offset + 4..offset + 36]);
+        items.push(Inventory::new(u32_at(bytes, offset), hash));
+        offset += 36;
+    }
+    Ok((items, offset))
+}
+
+fn nonce_from_bytes(bytes: &[u8]) -> Result<(u64, usize), BitcoinError> {
+    require(bytes, "nonce", 0, 8)?;
+    Ok((u64_at(bytes, 0), 8))
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MessagePayload {
+    Version(VersionMessage),
+    Verack,
+    Inv(Vec<Inventory>),
+    GetData(Vec<Inventory>),
+    Tx(BitcoinTransaction),
+    Ping(u64),
+    Pong(u64),
+    // Any other command, kept as raw bytes. Commands longer than 12 bytes
+    // are truncated when encoded.
+    Unknown { command: String, payload: Vec<u8> },
+}
+
+impl MessagePayload {
+    pub fn command(&self) -> &str {
+        match self {
+            MessagePayload::Version(_) => "version",
+            MessagePayload::Verack => "verack",
+            MessagePayload::Inv(_) => "inv",
+            MessagePayload::GetData(_) => "getdata",
+            MessagePayload::Tx(_) => "tx",
+            MessagePayload::Ping(_) => "ping",
+            MessagePayload::Pong(_) => "pong",
+            MessagePayload::Unknown { command, .. } => command,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            MessagePayload::Version(version) => version.to_bytes(),
+            MessagePayload::Verack => Vec::new(),
+            MessagePayload::Inv(items) | MessagePayload::GetData(items) => {
+                inventory_to_bytes(items)
+            }
+            MessagePayload::Tx(tx) => tx.to_bytes(),
+            MessagePayload::Ping(nonce) | MessagePayload::Pong(nonce) => {
+                nonce.to_le_bytes().to_vec()
+            }
+            MessagePayload::Unknown { payload, .. } => payload.clone(),
+        }
+    }
+
+    fn decode(command: &str, payload: &[u8]) -> Result<Self, BitcoinError> {
+        Ok(match command {
+            "version" => {
+                MessagePayload::Version(decode_exact(payload, VersionMessage::from_bytes)?)
+            }
+            "verack" => {
+                decode_exact(payload, |_| Ok(((), 0)))?;
+                MessagePayload::Verack
+            }
+            "inv" => MessagePayload::Inv(decode_exact(payload, inventory_from_bytes)?),
+            "getdata" => MessagePayload::GetData(decode_exact(payload, inventory_from_bytes)?),
+            "tx" => MessagePayload::Tx(BitcoinTransaction::decode_exact(payload)?),
+            "ping" => MessagePayload::Ping(decode_exact(payload, nonce_from_bytes)?),
+            "pong" => MessagePayload::Pong(decode_exact(payload, nonce_from_bytes)?),
+            _ => MessagePayload::Unknown {
+                command: command.to_string(),
+                payload: payload.to_vec(),
+            },
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NetworkMessage {
+    pub magic: [u8; 4],
+    pub payload: MessagePayload,
+}
+
+impl NetworkMessage {
+    pub const HEADER_SIZE: usize = 24;
+
+    pub fn new(magic: [u8; 4], payload: MessagePayload) -> Self {
+        NetworkMessage { magic, payload }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        frame(self.magic, self.payload.command(), &self.payload.to_bytes())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        require(bytes, "message header", 0, Self::HEADER_SIZE)?;
+        let mut magic = [0u8; 4];
+        magic.copy_from_slice(&bytes[..4]);
+        let command = parse_command(&bytes[4..16])?;
+
+        let payload_len = u32_at(bytes, 16) as usize;
+        require(bytes, "payload", Self::HEADER_SIZE, payload_len)?;
+        let end = Self::HEADER_SIZE + payload_len;
+        let payload = &bytes[Self::HEADER_SIZE..end];
+        if bytes[20..24] != hash::sha256d(payload)[..4] {
+            return Err(BitcoinError::invalid(20, "checksum mismatch"));
+        }
+
+        let payload =
+            MessagePayload::decode(command, payload).map_err(|e| e.at(Self::HEADER_SIZE))?;
+        Ok((NetworkMessage::new(magic, payload), end))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+}
+
+// Header plus payload: magic, NUL-padded command, length and the first four
+// bytes of the payload's double SHA-256.
+pub(crate) fn frame(magic: [u8; 4], command: &str, payload: &[u8]) -> Vec<u8> {
+    let mut padded = [0u8; 12];
+    let len = command.len().min(12);
+    padded[..len].copy_from_slice(&command.as_bytes()[..len]);
+
+    let mut message = Vec::with_capacity(NetworkMessage::HEADER_SIZE + payload.len());
+    message.extend_from_slice(&magic);
+    message.extend_from_slice(&padded);
+    message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    message.extend_from_slice(&hash::sha256d(payload)[..4]);
+    message.extend_from_slice(payload);
+    message
+}
+
+// Printable ASCII followed only by NUL padding.
+fn parse_command(raw: &[u8]) -> Result<&str, BitcoinError> {
+    let len = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+    let (name, padding) = raw.split_at(len);
+    if !name.iter().all(|b| b.is_ascii_graphic()) || padding.iter().any(|&b| b != 0) {
+        return Err(BitcoinError::invalid(4, "malformed command"));
+    }
+    Ok(core::str::from_utf8(name).expect("command is ASCII"))
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
+
+fn u64_at(bytes: &[u8], i: usize) -> u64 {
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(&bytes[i..i + 8]);
+    u64::from_le_bytes(raw)
+}
@@ -0,0 +1,147 @@
+macro_rules! opcodes {
+    ($($variant:ident = $byte:literal => $name:literal,)*) => {
+        #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+        #[repr(u8)]
+        pub enum Opcode {
+            $($variant = $byte,)*
+        }
+
+        impl Opcode {
+            pub fn from_byte(byte: u8) -> Option<Opcode> {
+                match byte {
+                    $($byte => Some(Opcode::$variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Opcode::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+// Direct pushes (0x01..=0x4b) are not listed; the script parser yields their
+// data instead.
+opcodes! {
+    False = 0x00 => "OP_0",
+    PushData1 = 0x4c => "OP_PUSHDATA1",
+    PushData2 = 0x4d => "OP_PUSHDATA2",
+    PushData4 = 0x4e => "OP_PUSHDATA4",
+    OneNegate = 0x4f => "OP_1NEGATE",
+    Reserved = 0x50 => "OP_RESERVED",
+    One = 0x51 => "OP_1",
+    Two = 0x52 => "OP_2",
+    Three = 0x53 => "OP_3",
+    Four = 0x54 => "OP_4",
+    Five = 0x55 => "OP_5",
+    Six = 0x56 => "OP_6",
+    Seven = 0x57 => "OP_7",
+    Eight = 0x58 => "OP_8",
+    Nine = 0x59 => "OP_9",
+    Ten = 0x5a => "OP_10",
+    Eleven = 0x5b => "OP_11",
+    Twelve = 0x5c => "OP_12",
+    Thirteen = 0x5d => "OP_13",
+    Fourteen = 0x5e => "OP_14",
+    Fifteen = 0x5f => "OP_15",
+    Sixteen = 0x60 => "OP_16",
+    Nop = 0x61 => "OP_NOP",
+    Ver = 0x62 => "OP_VER",
+    If = 0x63 => "OP_IF",
+    NotIf = 0x64 => "OP_NOTIF",
+    VerIf = 0x65 => "OP_VERIF",
+    VerNotIf = 0x66 => "OP_VERNOTIF",
+    Else = 0x67 => "OP_ELSE",
+    EndIf = 0x68 => "OP_ENDIF",
+    Verify = 0x69 => "OP_VERIFY",
+    Return = 0x6a => "OP_RETURN",
+    ToAltStack = 0x6b => "OP_TOALTSTACK",
+    FromAltStack = 0x6c => "OP_FROMALTSTACK",
+    TwoDrop = 0x6d => "OP_2DROP",
+    TwoDup = 0x6e => "OP_2DUP",
+    ThreeDup = 0x6f => "OP_3DUP",
+    TwoOver = 0x70 => "OP_2OVER",
+    TwoRot = 0x71 => "OP_2ROT",
+    TwoSwap = 0x72 => "OP_2SWAP",
+    IfDup = 0x73 => "OP_IFDUP",
+    Depth = 0x74 => "OP_DEPTH",
+    Drop = 0x75 => "OP_DROP",
+    Dup = 0x76 => "OP_DUP",
+    Nip = 0x77 => "OP_NIP",
+    Over = 0x78 => "OP_OVER",
+    Pick = 0x79 => "OP_PICK",
+    Roll = 0x7a => "OP_ROLL",
+    Rot = 0x7b => "OP_ROT",
+    Swap = 0x7c => "OP_SWAP",
+    Tuck = 0x7d => "OP_TUCK",
+    Cat = 0x7e => "OP_CAT",
+    Substr = 0x7f => "OP_SUBSTR",
+    Left = 0x80 => "OP_LEFT",
+    Right = 0x81 => "OP_RIGHT",
+    Size = 0x82 => "OP_SIZE",
+    Invert = 0x83 => "OP_INVERT",
+    And = 0x84 => "OP_AND",
+    Or = 0x85 => "OP_OR",
+    Xor = 0x86 => "OP_XOR",
+    Equal = 0x87 => "OP_EQUAL",
+    EqualVerify = 0x88 => "OP_EQUALVERIFY",
+    Reserved1 = 0x89 => "OP_RESERVED1",
+    Reserved2 = 0x8a => "OP_RESERVED2",
+    OneAdd = 0x8b => "OP_1ADD",
+    OneSub = 0x8c => "OP_1SUB",
+    TwoMul = 0x8d => "OP_2MUL",
+    TwoDiv = 0x8e => "OP_2DIV",
+    Negate = 0x8f => "OP_NEGATE",
+    Abs = 0x90 => "OP_ABS",
+    Not = 0x91 => "OP_NOT",
+    ZeroNotEqual = 0x92 => "OP_0NOTEQUAL",
+    Add = 0x93 => "OP_ADD",
+    Sub = 0x94 => "OP_SUB",
+    Mul = 0x95 => "OP_MUL",
+    Div = 0x96 => "OP_DIV",
+    Mod = 0x97 => "OP_MOD",
+    LShift = 0x98 => "OP_LSHIFT",
+    RShift = 0x99 => "OP_RSHIFT",
+    BoolAnd = 0x9a => "OP_BOOLAND",
+    BoolOr = 0x9b => "OP_BOOLOR",
+    NumEqual = 0x9c => "OP_NUMEQUAL",
+    NumEqualVerify = 0x9d => "OP_NUMEQUALVERIFY",
+    NumNotEqual = 0x9e => "OP_NUMNOTEQUAL",
+    LessThan = 0x9f => "OP_LESSTHAN",
+    GreaterThan = 0xa0 => "OP_GREATERTHAN",
+    LessThanOrEqual = 0xa1 => "OP_LESSTHANOREQUAL",
+    GreaterThanOrEqual = 0xa2 => "OP_GREATERTHANOREQUAL",
+    Min = 0xa3 => "OP_MIN",
+    Max = 0xa4 => "OP_MAX",
+    Within = 0xa5 => "OP_WITHIN",
+    Ripemd160 = 0xa6 => "OP_RIPEMD160",
+    Sha1 = 0xa7 => "OP_SHA1",
+    Sha256 = 0xa8 => "OP_SHA256",
+    Hash160 = 0xa9 => "OP_HASH160",
+    Hash256 = 0xaa => "OP_HASH256",
+    CodeSeparator = 0xab => "OP_CODESEPARATOR",
+    CheckSig = 0xac => "OP_CHECKSIG",
+    CheckSigVerify = 0xad => "OP_CHECKSIGVERIFY",
+    CheckMultiSig = 0xae => "OP_CHECKMULTISIG",
+    CheckMultiSigVerify = 0xaf => "OP_CHECKMULTISIGVERIFY",
+    Nop1 = 0xb0 => "OP_NOP1",
+    CheckLockTimeVerify = 0xb1 => "OP_CHECKLOCKTIMEVERIFY",
+    CheckSequenceVerify = 0xb2 => "OP_CHECKSEQUENCEVERIFY",
+    Nop4 = 0xb3 => "OP_NOP4",
+    Nop5 = 0xb4 => "OP_NOP5",
+    Nop6 = 0xb5 => "OP_NOP6",
+    Nop7 = 0xb6 => "OP_NOP7",
+    Nop8 = 0xb7 => "OP_NOP8",
+    Nop9 = 0xb8 => "OP_NOP9",
+    Nop10 = 0xb9 => "OP_NOP10",
+    CheckSigAdd = 0xba => "OP_CHECKSIGADD",
+}
+
+impl Opcode {
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+}
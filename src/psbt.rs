@@ -0,0 +1,314 @@
+use crate::{
+    BitcoinError, BitcoinTransaction, Script, SighashType, TransactionOutput, Witness, decode_exact,
+};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const MAGIC: [u8; 5] = *b"psbt\xff";
+
+const GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const IN_NON_WITNESS_UTXO: u8 = 0x00;
+const IN_WITNESS_UTXO: u8 = 0x01;
+const IN_PARTIAL_SIG: u8 = 0x02;
+const IN_SIGHASH_TYPE: u8 = 0x03;
+const IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+
+/// Key-value pairs of one PSBT map in encoding order. Keys include their
+/// leading type byte.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PsbtMap {
+    pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl PsbtMap {
+    pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    /// Replaces the value if the key is already present.
+    pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    pub fn entries_of_type(&self, key_type: u8) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.first() == Some(&key_type))
+            .map(|(k, v)| (&k[1..], v.as_slice()))
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        for (key, value) in &self.entries {
+            out.extend(Script::new(key.clone()).to_bytes());
+            out.extend(Script::new(value.clone()).to_bytes());
+        }
+        out.push(0x00);
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut map = PsbtMap::default();
+        let mut offset = 0;
+        loop {
+            let key_start = offset;
+            let (key, key_bytes) = Script::from_bytes(&bytes[offset..])
+                .map_err(|e| e.at(offset).in_field("PSBT key"))?;
+            offset += key_bytes;
+            if key.is_empty() {
+                return Ok((map, offset));
+            }
+            let (value, value_bytes) = Script::from_bytes(&bytes[offset..])
+                .map_err(|e| e.at(offset).in_field("PSBT value"))?;
+            offset += value_bytes;
+            if map.get(&key).is_some() {
+                return Err(BitcoinError::invalid(key_start, "duplicate PSBT key"));
+            }
+            map.entries.push((key.bytes, value.bytes));
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PsbtInput {
+    pub map: PsbtMap,
+}
+
+impl PsbtInput {
+    pub fn non_witness_utxo(&self) -> Result<Option<BitcoinTransaction>, BitcoinError> {
+        self.map
+            .get(&[IN_NON_WITNESS_UTXO])
+            .map(BitcoinTransaction::decode_exact)
+            .transpose()
+    }
+
+    pub fn set_non_witness_utxo(&mut self, tx: &BitcoinTransaction) {
+        self.map.insert(vec![IN_NON_WITNESS_UTXO], tx.to_bytes());
+    }
+
+    pub fn witness_utxo(&self) -> Result<Option<TransactionOutput>, BitcoinError> {
+        self.map
+            .get(&[IN_WITNESS_UTXO])
+            .map(TransactionOutput::decode_exact)
+            .transpose()
+    }
+
+    pub fn set_witness_utxo(&mut self, output: &TransactionOutput) {
+        self.map.insert(vec![IN_WITNESS_UTXO], output.to_bytes());
+    }
+
+    /// (public key, signature) pairs.
+    pub fn partial_sigs(&self) -> Vec<(&[u8], &[u8])> {
+        self.map.entries_of_type(IN_PARTIAL_SIG).collect()
+    }
+
+    pub fn add_partial_sig(&mut self, pubkey: &[u8], signature: Vec<u8>) {
+        let mut key = vec![IN_PARTIAL_SIG];
+        key.extend_from_slice(pubkey);
+        self.map.insert(key, signature);
+    }
+
+    pub fn sighash_type(&self) -> Result<Option<SighashType>, BitcoinError> {
+        let Some(value) = self.map.get(&[IN_SIGHASH_TYPE]) else {
+            return Ok(None);
+        };
+        let raw: [u8; 4] = value
+            .try_into()
+            .map_err(|_| BitcoinError::invalid(0, "sighash type must be 4 bytes"))?;
+        SighashType::from_u32(u32::from_le_bytes(raw))
+            .map(Some)
+            .ok_or(BitcoinError::invalid(0, "unknown sighash type"))
+    }
+
+    pub fn final_script_sig(&self) -> Option<Script> {
+        self.map
+            .get(&[IN_FINAL_SCRIPTSIG])
+            .map(|bytes| Script::new(bytes.to_vec()))
+    }
+
+    pub fn set_final_script_sig(&mut self, script_sig: &Script) {
+        self.map
+            .insert(vec![IN_FINAL_SCRIPTSIG], script_sig.bytes.clone());
+    }
+
+    pub fn final_script_witness(&self) -> Result<Option<Witness>, BitcoinError> {
+        self.map
+            .get(&[IN_FINAL_SCRIPTWITNESS])
+            .map(Witness::decode_exact)
+            .transpose()
+    }
+
+    pub fn set_final_script_witness(&mut self, witness: &Witness) {
+        self.map
+            .insert(vec![IN_FINAL_SCRIPTWITNESS], witness.to_bytes());
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PsbtOutput {
+    pub map: PsbtMap,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Psbt {
+    pub unsigned_tx: BitcoinTransaction,
+    // Global entries other than the unsigned transaction.
+    pub global: PsbtMap,
+    pub inputs: Vec<PsbtInput>,
+    pub outputs: Vec<PsbtOutput>,
+}
+
+impl Psbt {
+    pub fn new(unsigned_tx: BitcoinTransaction) -> Result<Self, BitcoinError> {
+        check_unsigned(&unsigned_tx)?;
+        Ok(Psbt {
+            inputs: vec![PsbtInput::default(); unsigned_tx.inputs.len()],
+            outputs: vec![PsbtOutput::default(); unsigned_tx.outputs.len()],
+            unsigned_tx,
+            global: PsbtMap::default(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        let mut global = PsbtMap::default();
+        global
+            .entries
+            .push((vec![GLOBAL_UNSIGNED_TX], self.unsigned_tx.to_legacy_bytes()));
+        global.entries.extend(self.global.entries.iter().cloned());
+        global.encode_into(&mut bytes);
+        for input in &self.inputs {
+            input.map.encode_into(&mut bytes);
+        }
+        for output in &self.outputs {
+            output.map.encode_into(&mut bytes);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        if !bytes.starts_with(&MAGIC) {
+            return Err(BitcoinError::invalid(0, "missing PSBT magic"));
+        }
+        let mut offset = MAGIC.len();
+        let (mut global, global_bytes) =
+            PsbtMap::from_bytes(&bytes[offset..]).map_err(|e| e.at(offset))?;
+        offset += global_bytes;
+
+        let Some(position) = global
+            .entries
+            .iter()
+            .position(|(key, _)| key[..] == [GLOBAL_UNSIGNED_TX])
+        else {
+            return Err(BitcoinError::invalid(
+                MAGIC.len(),
+                "missing unsigned transaction",
+            ));
+        };
+        let (_, tx_bytes) = global.entries.remove(position);
+        let unsigned_tx = BitcoinTransaction::decode_exact(&tx_bytes)
+            .and_then(|tx| check_unsigned(&tx).map(|_| tx))
+            .map_err(|e| e.at(MAGIC.len()))?;
+
+        let mut inputs = Vec::new();
+        for _ in 0..unsigned_tx.inputs.len() {
+            let (map, map_bytes) =
+                PsbtMap::from_bytes(&bytes[offset..]).map_err(|e| e.at(offset))?;
+            inputs.push(PsbtInput { map });
+            offset += map_bytes;
+        }
+        let mut outputs = Vec::new();
+        for _ in 0..unsigned_tx.outputs.len() {
+            let (map, map_bytes) =
+                PsbtMap::from_bytes(&bytes[offset..]).map_err(|e| e.at(offset))?;
+            outputs.push(PsbtOutput { map });
+            offset += map_bytes;
+        }
+
+        let psbt = Psbt {
+            unsigned_tx,
+            global,
+            inputs,
+            outputs,
+        };
+        Ok((psbt, offset))
+    }
+
+    pub fn decode_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        decode_exact(bytes, Self::from_bytes)
+    }
+
+    /// The base64 form used by wallets and Core's PSBT RPCs.
+    pub fn to_base64(&self) -> String {
+        base64_encode(&self.to_bytes())
+    }
+
+    pub fn from_base64(s: &str) -> Result<Self, BitcoinError> {
+        let bytes = base64_decode(s).ok_or(BitcoinError::invalid(0, "invalid base64"))?;
+        Self::decode_exact(&bytes)
+    }
+}
+
+fn check_unsigned(tx: &BitcoinTransaction) -> Result<(), BitcoinError> {
+    if tx.is_segwit() || tx.inputs.iter().any(|input| !input.script_sig.is_empty()) {
+        return Err(BitcoinError::invalid(
+            0,
+            "PSBT transaction must be unsigned",
+        ));
+    }
+    // Encoded without a witness, a transaction with no inputs reads as a
+    // segwit marker.
+    if tx.inputs.is_empty() {
+        return Err(BitcoinError::invalid(0, "PSBT transaction has no inputs"));
+    }
+    Ok(())
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut group = [0u8; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let n = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.as_bytes();
+    if !s.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    for (i, chunk) in s.chunks(4).enumerate() {
+        let last = i == s.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut n = 0u32;
+        for &c in &chunk[..4 - padding] {
+            let digit = BASE64_ALPHABET.iter().position(|&a| a == c)?;
+            n = (n << 6) | digit as u32;
+        }
+        n <<= 6 * padding;
+        out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+    Some(out)
+}
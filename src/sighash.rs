@@ -0,0 +1,220 @@
+use crate::hash;
+use crate::{BitcoinError, BitcoinTransaction, Script, TransactionOutput};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum SighashType {
+    All,
+    None,
+    Single,
+    AllPlusAnyoneCanPay,
+    NonePlusAnyoneCanPay,
+    SinglePlusAnyoneCanPay,
+}
+
+impl SighashType {
+    const ANYONECANPAY: u32 = 0x80;
+
+    pub fn from_u32(value: u32) -> Option<SighashType> {
+        match value {
+            0x01 => Some(SighashType::All),
+            0x02 => Some(SighashType::None),
+            0x03 => Some(SighashType::Single),
+            0x81 => Some(SighashType::AllPlusAnyoneCanPay),
+            0x82 => Some(SighashType::NonePlusAnyoneCanPay),
+            0x83 => Some(SighashType::SinglePlusAnyoneCanPay),
+            _ => None,
+        }
+    }
+
+    pub fn to_u32(self) -> u32 {
+        match self {
+            SighashType::All => 0x01,
+            SighashType::None => 0x02,
+            SighashType::Single => 0x03,
+            SighashType::AllPlusAnyoneCanPay => 0x81,
+            SighashType::NonePlusAnyoneCanPay => 0x82,
+            SighashType::SinglePlusAnyoneCanPay => 0x83,
+        }
+    }
+
+    pub fn anyone_can_pay(self) -> bool {
+        self.to_u32() & Self::ANYONECANPAY != 0
+    }
+
+    // The type with the ANYONECANPAY flag cleared.
+    fn base(self) -> SighashType {
+        match self {
+            SighashType::All | SighashType::AllPlusAnyoneCanPay => SighashType::All,
+            SighashType::None | SighashType::NonePlusAnyoneCanPay => SighashType::None,
+            SighashType::Single | SighashType::SinglePlusAnyoneCanPay => SighashType::Single,
+        }
+    }
+}
+
+// Holds the BIP-143 hashPrevouts, hashSequence and hashOutputs so that signing
+// every input of a transaction hashes them only once.
+#[derive(Debug, Clone)]
+pub struct SighashCache<'a> {
+    tx: &'a BitcoinTransaction,
+    hash_prevouts: [u8; 32],
+    hash_sequence: [u8; 32],
+    hash_outputs: [u8; 32],
+}
+
+impl<'a> SighashCache<'a> {
+    pub fn new(tx: &'a BitcoinTransaction) -> Self {
+        SighashCache {
+            tx,
+            hash_prevouts: hash::sha256d(&tx.prevouts_bytes()),
+            hash_sequence: hash::sha256d(&tx.sequences_bytes()),
+            hash_outputs: hash::sha256d(&tx.outputs_bytes()),
+        }
+    }
+
+    /// BIP-143 signature hash; `value` is the amount of the output being spent.
+    pub fn segwit_signature_hash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], BitcoinError> {
+        let input = self
+            .tx
+            .inputs
+            .get(input_index)
+            .ok_or(BitcoinError::invalid(0, "input index out of range"))?;
+        let base = sighash_type.base();
+
+        let hash_prevouts = if sighash_type.anyone_can_pay() {
+            [0; 32]
+        } else {
+            self.hash_prevouts
+        };
+        let hash_sequence = if sighash_type.anyone_can_pay() || base != SighashType::All {
+            [0; 32]
+        } else {
+            self.hash_sequence
+        };
+        let hash_outputs = match base {
+            SighashType::All => self.hash_outputs,
+            SighashType::Single => match self.tx.outputs.get(input_index) {
+                Some(output) => hash::sha256d(&output.to_bytes()),
+                None => [0; 32],
+            },
+            _ => [0; 32],
+        };
+
+        let mut preimage = self.tx.version.to_le_bytes().to_vec();
+        preimage.extend_from_slice(&hash_prevouts);
+        preimage.extend_from_slice(&hash_sequence);
+        preimage.extend(input.previous_output.to_bytes());
+        preimage.extend(script_code.to_bytes());
+        preimage.extend_from_slice(&value.to_le_bytes());
+        preimage.extend_from_slice(&input.sequence.to_le_bytes());
+        preimage.extend_from_slice(&hash_outputs);
+        preimage.extend_from_slice(&self.tx.lock_time.to_le_bytes());
+        preimage.extend_from_slice(&sighash_type.to_u32().to_le_bytes());
+        Ok(hash::sha256d(&preimage))
+    }
+}
+
+impl BitcoinTransaction {
+    /// Pre-segwit signature hash with OP_CODESEPARATORs removed from `script_code`.
+    /// `sighash_type` is the raw hashtype: any value is accepted and hashed
+    /// in full, with the base type taken from its low 5 bits as Core does.
+    pub fn signature_hash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: u32,
+    ) -> Result<[u8; 32], BitcoinError> {
+        if input_index >= self.inputs.len() {
+            return Err(BitcoinError::invalid(0, "input index out of range"));
+        }
+        // SIGHASH_SINGLE without a matching output signs the value 1 instead of
+        // failing, as the original client did.
+        if legacy_base(sighash_type) == SighashType::Single && input_index >= self.outputs.len() {
+            let mut one = [0; 32];
+            one[0] = 1;
+            return Ok(one);
+        }
+        let preimage = self.sighash_preimage(input_index, script_code, sighash_type)?;
+        Ok(hash::sha256d(&preimage))
+    }
+
+    /// The bytes signature_hash double-SHA256s. SIGHASH_SINGLE without a
+    /// matching output has no preimage and is an error here.
+    pub fn sighash_preimage(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: u32,
+    ) -> Result<Vec<u8>, BitcoinError> {
+        if input_index >= self.inputs.len() {
+            return Err(BitcoinError::invalid(0, "input index out of range"));
+        }
+        let base = legacy_base(sighash_type);
+        if base == SighashType::Single && input_index >= self.outputs.len() {
+            return Err(BitcoinError::invalid(
+                0,
+                "SIGHASH_SINGLE input has no matching output",
+            ));
+        }
+
+        let mut tx = BitcoinTransaction::with_outputs(
+            self.version,
+            self.inputs.clone(),
+            self.outputs.clone(),
+            self.lock_time,
+        );
+        for (i, input) in tx.inputs.iter_mut().enumerate() {
+            if i == input_index {
+                input.script_sig = script_code.without_codeseparators();
+            } else {
+                input.script_sig = Script::default();
+                if base != SighashType::All {
+                    input.sequence = 0;
+                }
+            }
+        }
+        match base {
+            SighashType::None => tx.outputs.clear(),
+            SighashType::Single => {
+                tx.outputs.truncate(input_index + 1);
+                for output in &mut tx.outputs[..input_index] {
+                    *output = TransactionOutput::new(u64::MAX, Script::default());
+                }
+            }
+            _ => {}
+        }
+        if sighash_type & SighashType::ANYONECANPAY != 0 {
+            tx.inputs = vec![tx.inputs.swap_remove(input_index)];
+        }
+
+        let mut preimage = tx.to_legacy_bytes();
+        preimage.extend_from_slice(&sighash_type.to_le_bytes());
+        Ok(preimage)
+    }
+
+    pub fn segwit_signature_hash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], BitcoinError> {
+        SighashCache::new(self).segwit_signature_hash(input_index, script_code, value, sighash_type)
+    }
+}
+
+// Legacy signing treats any base type other than NONE and SINGLE as ALL.
+fn legacy_base(sighash_type: u32) -> SighashType {
+    match sighash_type & 0x1f {
+        0x02 => SighashType::None,
+        0x03 => SighashType::Single,
+        _ => SighashType::All,
+    }
+}
@@ -110,4 +110,3481 @@ mod tests {
         assert!(output.contains("Lock Time: 0"));
         assert!(output.contains("Previous Output Vout: 7"));
     }
+
+    #[test]
+    fn test_script_length_beyond_u16() {
+        let script_data = vec![0x51; u16::MAX as usize + 10];
+        let script = Script::new(script_data);
+        let bytes = script.to_bytes();
+        assert_eq!(bytes[0], 0xFE);
+        let (parsed, consumed) = Script::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, script);
+        assert_eq!(consumed, bytes.len());
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert_eq!(
+            Script::from_bytes(truncated),
+            Err(BitcoinError::InsufficientBytes {
+                field: "script",
+                offset: 5,
+                needed: script.len(),
+                available: script.len() - 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_script_length_overflow_rejected() {
+        let bytes = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+        assert!(matches!(
+            Script::from_bytes(&bytes),
+            Err(BitcoinError::InvalidFormat { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes_with_warnings() {
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(vec![]),
+            0xFFFFFFFF,
+        );
+        let tx = BitcoinTransaction::new(7, vec![input], 0);
+        let bytes = tx.to_bytes();
+
+        let (parsed, warnings) = BitcoinTransaction::from_bytes_with_warnings(&bytes).unwrap();
+        assert_eq!(parsed, tx);
+        assert_eq!(
+            warnings,
+            vec![
+                "non-standard version 7".to_string(),
+                "empty scriptSig on non-segwit input 0".to_string(),
+            ]
+        );
+
+        let clean = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01, 0x02]),
+                0xFFFFFFFF,
+            )],
+            0,
+        );
+        let (_, warnings) =
+            BitcoinTransaction::from_bytes_with_warnings(&clean.to_bytes()).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_uniform_sequences() {
+        let input = |sequence| {
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01]),
+                sequence,
+            )
+        };
+        let uniform = BitcoinTransaction::new(2, vec![input(0xFFFFFFFD), input(0xFFFFFFFD)], 0);
+        assert!(uniform.uniform_sequences());
+
+        let mixed = BitcoinTransaction::new(2, vec![input(0xFFFFFFFD), input(0xFFFFFFFF)], 0);
+        assert!(!mixed.uniform_sequences());
+    }
+
+    #[test]
+    fn test_version_and_lock_time_bytes() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01, 0x02]),
+                0xFFFFFFFF,
+            )],
+            0x00BC614E,
+        );
+        let bytes = tx.to_bytes();
+        assert_eq!(tx.version_bytes(), [0x02, 0x00, 0x00, 0x00]);
+        assert_eq!(tx.version_bytes(), bytes[..4]);
+        assert_eq!(tx.lock_time_bytes(), [0x4E, 0x61, 0xBC, 0x00]);
+        assert_eq!(tx.lock_time_bytes(), bytes[bytes.len() - 4..]);
+    }
+
+    #[test]
+    fn test_skeleton_size() {
+        let input = |script: Vec<u8>| {
+            TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(script), 0)
+        };
+        let outputs = vec![TransactionOutput::new(1_000, Script::new(vec![0x51]))];
+        let tx = BitcoinTransaction::with_outputs(
+            1,
+            vec![input(vec![0xAA; 107]), input(vec![0xBB; 300])],
+            outputs.clone(),
+            0,
+        );
+        let stripped =
+            BitcoinTransaction::with_outputs(1, vec![input(vec![]), input(vec![])], outputs, 0);
+        assert_eq!(tx.skeleton_size(), stripped.to_bytes().len());
+        assert_eq!(tx.skeleton_size(), 4 + 1 + 2 * 41 + 1 + 10 + 4);
+    }
+
+    #[test]
+    fn test_compact_size_encode_into() {
+        for value in [0u64, 252, 253, 65535, 65536, 4294967295, 4294967296] {
+            let cs = CompactSize::new(value);
+            let mut out = vec![0xAB];
+            cs.encode_into(&mut out);
+            assert_eq!(out[0], 0xAB);
+            assert_eq!(out[1..], cs.to_bytes()[..]);
+        }
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let make = |lock_time| {
+            BitcoinTransaction::new(
+                1,
+                vec![TransactionInput::new(
+                    OutPoint::new(dummy_txid(1), 0),
+                    Script::new(vec![0x01, 0x02]),
+                    0xFFFFFFFF,
+                )],
+                lock_time,
+            )
+        };
+        assert_eq!(make(0).cache_key(), make(0).cache_key());
+        assert_eq!(make(0).cache_key(), make(0).clone().cache_key());
+        assert_ne!(make(0).cache_key(), make(1).cache_key());
+    }
+
+    #[test]
+    fn test_opcode_histogram() {
+        let mut p2pkh_like = vec![0x76, 0xA9, 0x14];
+        p2pkh_like.extend_from_slice(&[0xAC; 20]);
+        p2pkh_like.extend_from_slice(&[0x88, 0xAC]);
+        let inputs = vec![
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(p2pkh_like),
+                0xFFFFFFFF,
+            ),
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(2), 0),
+                Script::new(vec![0x4C, 0x02, 0xAC, 0xAC, 0xAC]),
+                0xFFFFFFFF,
+            ),
+        ];
+        let tx = BitcoinTransaction::new(1, inputs, 0);
+        let histogram = tx.opcode_histogram();
+        assert_eq!(histogram.get(&0x76), Some(&1));
+        assert_eq!(histogram.get(&0xA9), Some(&1));
+        assert_eq!(histogram.get(&0x14), Some(&1));
+        assert_eq!(histogram.get(&0x88), Some(&1));
+        assert_eq!(histogram.get(&0x4C), Some(&1));
+        // Only the two real OP_CHECKSIGs count, not the 0xAC bytes inside pushes.
+        assert_eq!(histogram.get(&0xAC), Some(&2));
+        assert_eq!(histogram.len(), 6);
+    }
+
+    #[test]
+    fn test_read_count_checked() {
+        assert_eq!(read_count_checked(&[0x02, 0, 0, 0, 0], 2), Ok((2, 1)));
+        let inflated = Err(BitcoinError::InvalidFormat {
+            offset: 0,
+            reason: "count exceeds remaining bytes",
+        });
+        assert_eq!(read_count_checked(&[0x03, 0, 0, 0, 0], 2), inflated);
+        assert_eq!(
+            read_count_checked(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], 41),
+            inflated
+        );
+
+        let tx = BitcoinTransaction::new(
+            1,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![]),
+                0xFFFFFFFF,
+            )],
+            0,
+        );
+        let mut bytes = tx.to_bytes();
+        bytes[4] = 0xFC;
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&bytes),
+            Err(BitcoinError::InvalidFormat {
+                offset: 4,
+                reason: "count exceeds remaining bytes",
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_annotated_hex() {
+        let tx = BitcoinTransaction::with_outputs(
+            1,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(0xAB), 3),
+                Script::new(vec![0xDE, 0xAD]),
+                0xFFFFFFFE,
+            )],
+            vec![TransactionOutput::new(50_000, Script::new(vec![0x51]))],
+            500,
+        );
+        let expected = "\
+01000000  # version
+01  # input count
+00000000000000000000000000000000000000000000000000000000000000ab  # input 0 txid
+03000000  # input 0 vout
+02  # input 0 scriptSig length
+dead  # input 0 scriptSig
+feffffff  # input 0 sequence
+01  # output count
+50c3000000000000  # output 0 value
+01  # output 0 scriptPubKey length
+51  # output 0 scriptPubKey
+f4010000  # lock_time";
+        assert_eq!(tx.to_annotated_hex(), expected);
+
+        let hex_only: String = tx
+            .to_annotated_hex()
+            .lines()
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(hex_only, hex::encode(tx.to_bytes()));
+    }
+
+    #[test]
+    fn test_witness_program() {
+        let mut p2wpkh = vec![0x00, 0x14];
+        p2wpkh.extend_from_slice(&[0x11; 20]);
+        assert_eq!(
+            Script::new(p2wpkh).witness_program(),
+            Some((0, vec![0x11; 20]))
+        );
+
+        let mut p2tr = vec![0x51, 0x20];
+        p2tr.extend_from_slice(&[0x22; 32]);
+        assert_eq!(
+            Script::new(p2tr).witness_program(),
+            Some((1, vec![0x22; 32]))
+        );
+
+        assert_eq!(Script::new(vec![0x00, 0x01, 0xFF]).witness_program(), None);
+        assert_eq!(
+            Script::new(vec![0x76, 0x02, 0x01, 0x02]).witness_program(),
+            None
+        );
+        assert_eq!(Script::new(vec![0x00, 0x14, 0x01]).witness_program(), None);
+    }
+
+    #[test]
+    fn test_witness_program_strict() {
+        let mut too_long = vec![0x00, 0x29];
+        too_long.extend_from_slice(&[0x33; 41]);
+        let script = Script::new(too_long);
+        assert_eq!(
+            script.witness_program_strict(),
+            Err(BitcoinError::InvalidWitnessProgram)
+        );
+        assert_eq!(script.witness_program(), None);
+
+        assert_eq!(
+            Script::new(vec![0x51, 0x01, 0x01]).witness_program_strict(),
+            Err(BitcoinError::InvalidWitnessProgram)
+        );
+        assert_eq!(
+            Script::new(vec![0x76, 0xA9]).witness_program_strict(),
+            Ok(None)
+        );
+
+        let mut p2wsh = vec![0x00, 0x20];
+        p2wsh.extend_from_slice(&[0x44; 32]);
+        assert_eq!(
+            Script::new(p2wsh).witness_program_strict(),
+            Ok(Some((0, vec![0x44; 32])))
+        );
+    }
+
+    #[test]
+    fn test_reorder_inputs() {
+        let inputs: Vec<_> = (0..3)
+            .map(|i| {
+                TransactionInput::new(
+                    OutPoint::new(dummy_txid(i), i as u32),
+                    Script::new(vec![i]),
+                    0xFFFFFFFF,
+                )
+            })
+            .collect();
+        let mut tx = BitcoinTransaction::new(2, inputs.clone(), 0);
+
+        tx.reorder_inputs(&[2, 0, 1]).unwrap();
+        assert_eq!(
+            tx.inputs,
+            vec![inputs[2].clone(), inputs[0].clone(), inputs[1].clone()]
+        );
+
+        let before = tx.clone();
+        assert!(matches!(
+            tx.reorder_inputs(&[0, 0, 1]),
+            Err(BitcoinError::InvalidFormat { .. })
+        ));
+        assert!(matches!(
+            tx.reorder_inputs(&[0, 1, 3]),
+            Err(BitcoinError::InvalidFormat { .. })
+        ));
+        assert!(matches!(
+            tx.reorder_inputs(&[0, 1]),
+            Err(BitcoinError::InvalidFormat { .. })
+        ));
+        assert_eq!(tx, before);
+
+        // Witnesses follow their inputs.
+        let mut segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let original = segwit.clone();
+        segwit.reorder_inputs(&[1, 0]).unwrap();
+        assert_eq!(segwit.inputs[0], original.inputs[1]);
+        assert_eq!(segwit.witnesses[0], original.witnesses[1]);
+        assert_eq!(segwit.witnesses[1], original.witnesses[0]);
+        assert!(segwit.witnesses[1].is_empty());
+        segwit.reorder_inputs(&[1, 0]).unwrap();
+        assert_eq!(segwit, original);
+    }
+
+    #[test]
+    fn test_prevouts_bytes() {
+        let outpoints = [
+            OutPoint::new(dummy_txid(1), 0),
+            OutPoint::new(dummy_txid(2), 5),
+        ];
+        let inputs = outpoints
+            .iter()
+            .map(|outpoint| TransactionInput::new(outpoint.clone(), Script::new(vec![0x01]), 0))
+            .collect::<Vec<_>>();
+        let tx = BitcoinTransaction::new(2, inputs, 0);
+        let bytes = tx.prevouts_bytes();
+        assert_eq!(bytes.len(), tx.inputs.len() * 36);
+        assert_eq!(bytes[..36], outpoints[0].to_bytes()[..]);
+        assert_eq!(bytes[36..], outpoints[1].to_bytes()[..]);
+    }
+
+    #[test]
+    fn test_sequences_bytes() {
+        let inputs = [0xFFFFFFFD, 0x00000001, 0xFFFFFFFF]
+            .into_iter()
+            .map(|sequence| {
+                TransactionInput::new(
+                    OutPoint::new(dummy_txid(1), 0),
+                    Script::new(vec![]),
+                    sequence,
+                )
+            })
+            .collect::<Vec<_>>();
+        let tx = BitcoinTransaction::new(2, inputs, 0);
+        let bytes = tx.sequences_bytes();
+        assert_eq!(bytes.len(), tx.inputs.len() * 4);
+        assert_eq!(
+            bytes,
+            vec![
+                0xFD, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF
+            ]
+        );
+    }
+
+    #[test]
+    fn test_all_data_pushes() {
+        let mut first = vec![0x05];
+        first.extend_from_slice(b"hello");
+        first.push(0xAC);
+        let mut second = vec![0x4C, 0x03];
+        second.extend_from_slice(b"abc");
+        let inputs = vec![
+            TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(first), 0),
+            TransactionInput::new(OutPoint::new(dummy_txid(2), 0), Script::new(second), 0),
+        ];
+        let tx = BitcoinTransaction::new(1, inputs, 0);
+        assert_eq!(
+            tx.all_data_pushes(),
+            vec![b"hello".to_vec(), b"abc".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_verify_internal_consistency() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01, 0x02]),
+                0xFFFFFFFF,
+            )],
+            0,
+        );
+        let bytes = tx.to_bytes();
+        assert_eq!(
+            BitcoinTransaction::verify_internal_consistency(&bytes),
+            Ok(())
+        );
+
+        let mut padded = bytes.clone();
+        padded.push(0x00);
+        assert_eq!(
+            BitcoinTransaction::verify_internal_consistency(&padded),
+            Err(BitcoinError::InvalidFormat {
+                offset: bytes.len(),
+                reason: "trailing bytes",
+            })
+        );
+
+        let mut inflated_script = bytes.clone();
+        inflated_script[41] = 0x03;
+        assert_eq!(
+            BitcoinTransaction::verify_internal_consistency(&inflated_script),
+            Err(BitcoinError::InsufficientBytes {
+                field: "lock_time",
+                offset: 50,
+                needed: 4,
+                available: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chunks() {
+        let inputs = (0..3)
+            .map(|i| {
+                TransactionInput::new(
+                    OutPoint::new(dummy_txid(i), 0),
+                    Script::new(vec![i; i as usize]),
+                    0xFFFFFFFF,
+                )
+            })
+            .collect::<Vec<_>>();
+        let outputs = vec![
+            TransactionOutput::new(1, Script::new(vec![0x51])),
+            TransactionOutput::new(2, Script::new(vec![0x52])),
+        ];
+        let tx = BitcoinTransaction::with_outputs(2, inputs, outputs, 42);
+        let chunks: Vec<Vec<u8>> = tx.chunks().collect();
+        assert_eq!(chunks.len(), 2 + tx.inputs.len() + 1 + tx.outputs.len() + 1);
+        assert_eq!(chunks[2], tx.inputs[0].to_bytes());
+        assert_eq!(chunks[6], tx.outputs[0].to_bytes());
+        assert_eq!(chunks.concat(), tx.to_bytes());
+    }
+
+    #[test]
+    fn test_base_size() {
+        let input = |script: Vec<u8>| {
+            TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(script), 0)
+        };
+        let tx = BitcoinTransaction::new(
+            1,
+            vec![
+                input(vec![]),
+                input(vec![0xAA; 107]),
+                input(vec![0xBB; 300]),
+            ],
+            0,
+        );
+        assert_eq!(tx.base_size(), tx.to_bytes().len());
+        assert_eq!(BitcoinTransaction::new(1, vec![], 0).base_size(), 10);
+    }
+
+    #[test]
+    fn test_rbf_min_replacement_fee() {
+        assert_eq!(rbf_min_replacement_fee(1_000, 200, 200, 1), 1_200);
+        assert_eq!(rbf_min_replacement_fee(1_000, 200, 150, 2), 1_300);
+        // A much larger replacement is bound by the fee-rate rule instead.
+        assert_eq!(rbf_min_replacement_fee(10_000, 100, 1_000, 1), 100_001);
+    }
+
+    #[test]
+    fn test_length_prefixed_roundtrip() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01; 300]),
+                0xFFFFFFFF,
+            )],
+            7,
+        );
+        let mut buffer = Vec::new();
+        tx.write_length_prefixed(&mut buffer).unwrap();
+        tx.write_length_prefixed(&mut buffer).unwrap();
+        assert_eq!(buffer[..3], [0xFD, 0x61, 0x01]);
+
+        let mut cursor = std::io::Cursor::new(buffer);
+        assert_eq!(
+            BitcoinTransaction::read_length_prefixed(&mut cursor).unwrap(),
+            tx
+        );
+        assert_eq!(
+            BitcoinTransaction::read_length_prefixed(&mut cursor).unwrap(),
+            tx
+        );
+        assert_eq!(
+            BitcoinTransaction::read_length_prefixed(&mut cursor)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_parse_and_compare_size() {
+        let tx = BitcoinTransaction::new(
+            1,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01]),
+                0xFFFFFFFF,
+            )],
+            0,
+        );
+        let bytes = tx.to_bytes();
+        assert_eq!(
+            BitcoinTransaction::parse_and_compare_size(&bytes),
+            Ok((bytes.len(), bytes.len()))
+        );
+
+        let mut non_canonical = bytes.clone();
+        non_canonical.splice(4..5, [0xFD, 0x01, 0x00]);
+        assert_eq!(
+            BitcoinTransaction::parse_and_compare_size(&non_canonical),
+            Err(BitcoinError::NonCanonicalVarInt { offset: 4 })
+        );
+    }
+
+    #[test]
+    fn test_inputs_by_txid() {
+        let inputs = [(1, 0), (2, 0), (1, 3), (1, 1)]
+            .into_iter()
+            .map(|(txid, vout)| {
+                TransactionInput::new(
+                    OutPoint::new(dummy_txid(txid), vout),
+                    Script::new(vec![]),
+                    0,
+                )
+            })
+            .collect::<Vec<_>>();
+        let tx = BitcoinTransaction::new(2, inputs, 0);
+        let groups = tx.inputs_by_txid();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[&Txid(dummy_txid(1))], vec![0, 2, 3]);
+        assert_eq!(groups[&Txid(dummy_txid(2))], vec![1]);
+    }
+
+    #[test]
+    fn test_required_signatures() {
+        let mut p2pkh = vec![0x76, 0xA9, 0x14];
+        p2pkh.extend_from_slice(&[0x11; 20]);
+        p2pkh.extend_from_slice(&[0x88, 0xAC]);
+        assert_eq!(Script::new(p2pkh).required_signatures(), Some(1));
+
+        let mut p2wpkh = vec![0x00, 0x14];
+        p2wpkh.extend_from_slice(&[0x11; 20]);
+        assert_eq!(Script::new(p2wpkh).required_signatures(), Some(1));
+
+        let mut multisig = vec![0x52];
+        for i in 0..3 {
+            multisig.push(0x21);
+            multisig.push(0x02);
+            multisig.extend_from_slice(&[i; 32]);
+        }
+        multisig.extend_from_slice(&[0x53, 0xAE]);
+        assert_eq!(Script::new(multisig.clone()).required_signatures(), Some(2));
+
+        let mut wrong_count = multisig;
+        let len = wrong_count.len();
+        wrong_count[len - 2] = 0x54;
+        assert_eq!(Script::new(wrong_count).required_signatures(), None);
+
+        let mut p2sh = vec![0xA9, 0x14];
+        p2sh.extend_from_slice(&[0x11; 20]);
+        p2sh.push(0x87);
+        assert_eq!(Script::new(p2sh).required_signatures(), None);
+    }
+
+    #[test]
+    fn test_has_non_minimal_push() {
+        let mut minimal = vec![0x0A];
+        minimal.extend_from_slice(&[0x11; 10]);
+        minimal.extend_from_slice(&[0x00, 0x55, 0x02, 0xE8, 0x03, 0xB1]);
+        assert!(!Script::new(minimal).has_non_minimal_push());
+
+        let mut pushdata1 = vec![0x4C, 0x0A];
+        pushdata1.extend_from_slice(&[0x11; 10]);
+        assert!(Script::new(pushdata1).has_non_minimal_push());
+
+        assert!(Script::new(vec![0x01, 0x05]).has_non_minimal_push());
+        assert!(Script::new(vec![0x4C, 0x00]).has_non_minimal_push());
+        // A 1-byte push of 0x00 satisfies MINIMALDATA's push rule.
+        assert!(!Script::new(vec![0x01, 0x00]).has_non_minimal_push());
+        assert!(!Script::new(vec![0x02, 0x05, 0x00]).has_non_minimal_push());
+    }
+
+    #[test]
+    fn test_has_non_minimal_number() {
+        assert!(!Script::new(vec![0x02, 0xE8, 0x03, 0xB1]).has_non_minimal_number());
+        assert!(!Script::new(vec![0x02, 0xFF, 0x00]).has_non_minimal_number());
+        assert!(Script::new(vec![0x02, 0x05, 0x00]).has_non_minimal_number());
+        assert!(Script::new(vec![0x01, 0x00]).has_non_minimal_number());
+        // Longer pushes are data, not numbers.
+        assert!(!Script::new(vec![0x05, 0x01, 0, 0, 0, 0]).has_non_minimal_number());
+    }
+
+    #[test]
+    fn test_block_subsidy() {
+        assert_eq!(block_subsidy(0), 50_0000_0000);
+        assert_eq!(block_subsidy(209_999), 50_0000_0000);
+        assert_eq!(block_subsidy(210_000), 25_0000_0000);
+        assert_eq!(block_subsidy(840_000), 3_1250_0000);
+        assert_eq!(block_subsidy(6_930_000), 0);
+        assert_eq!(block_subsidy(u32::MAX), 0);
+    }
+
+    #[test]
+    fn test_to_legacy_bytes() {
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01, 0x02]),
+                0xFFFFFFFF,
+            )],
+            0,
+        );
+        let legacy = tx.to_legacy_bytes();
+        assert_eq!(legacy, tx.to_bytes());
+        assert_ne!(legacy[4], 0x00);
+        assert_eq!(BitcoinTransaction::from_bytes(&legacy).unwrap().0, tx);
+    }
+
+    #[test]
+    fn test_tx_output_roundtrip() {
+        let output = TransactionOutput::new(
+            5_000_000_000,
+            Script::new(vec![0x76, 0xA9, 0x14, 0x88, 0xAC]),
+        );
+        let bytes = output.to_bytes();
+        assert_eq!(bytes[..8], 5_000_000_000u64.to_le_bytes());
+        let (parsed, consumed) = TransactionOutput::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, output);
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(
+            TransactionOutput::from_bytes(&bytes[..7]),
+            Err(BitcoinError::InsufficientBytes {
+                field: "output value",
+                offset: 0,
+                needed: 8,
+                available: 7,
+            })
+        );
+    }
+
+    // Block 170: the first bitcoin payment, from Satoshi to Hal Finney.
+    const BLOCK_170_TX_HEX: &str = "0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000";
+
+    #[test]
+    fn test_mainnet_tx_roundtrip() {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        let (tx, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(tx.version, 1);
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.inputs[0].previous_output.vout, 0);
+        assert_eq!(tx.inputs[0].script_sig.len(), 72);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[0].value, 10_0000_0000);
+        assert_eq!(tx.outputs[1].value, 40_0000_0000);
+        assert_eq!(tx.outputs[0].script_pubkey.len(), 67);
+        assert_eq!(tx.outputs[0].script_pubkey[66], 0xAC);
+        assert_eq!(tx.lock_time, 0);
+        assert_eq!(tx.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_bitcoin_transaction_display_outputs() {
+        let tx = BitcoinTransaction::with_outputs(
+            1,
+            vec![],
+            vec![TransactionOutput::new(
+                1234,
+                Script::new(vec![0x6A, 0x01, 0xFF]),
+            )],
+            0,
+        );
+        let output = format!("{}", tx);
+        assert!(output.contains("Output 1:"));
+        assert!(output.contains("Value: 1234"));
+        assert!(output.contains("ScriptPubKey: length=3, bytes=6a01ff"));
+    }
+
+    #[test]
+    fn test_txid() {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            tx.txid().to_rpc_hex(),
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16"
+        );
+        // The block 170 input spends output 0 of the block 9 coinbase.
+        assert_eq!(
+            tx.inputs[0].previous_output.txid.to_rpc_hex(),
+            "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9"
+        );
+
+        let genesis_coinbase = hex::decode("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000").unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&genesis_coinbase).unwrap();
+        assert_eq!(
+            tx.txid().to_rpc_hex(),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+        assert_eq!(tx.txid().0[0], 0x3b);
+    }
+
+    #[test]
+    fn test_compact_size_rejects_non_canonical() {
+        let non_canonical: Vec<Vec<u8>> = vec![
+            vec![0xFD, 0x00, 0x00],
+            vec![0xFD, 0xFC, 0x00],
+            vec![0xFE, 0x00, 0x00, 0x00, 0x00],
+            vec![0xFE, 0xFF, 0xFF, 0x00, 0x00],
+            vec![0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00],
+        ];
+        for bytes in non_canonical {
+            assert_eq!(
+                CompactSize::from_bytes(&bytes),
+                Err(BitcoinError::NonCanonicalVarInt { offset: 0 }),
+                "{:02x?}",
+                bytes
+            );
+        }
+
+        for value in [
+            0u64,
+            0xFC,
+            0xFD,
+            0xFFFF,
+            0x10000,
+            0xFFFFFFFF,
+            0x100000000,
+            u64::MAX,
+        ] {
+            let bytes = CompactSize::new(value).to_bytes();
+            assert_eq!(
+                CompactSize::from_bytes(&bytes),
+                Ok((CompactSize::new(value), bytes.len()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_compact_size_validate_encoding() {
+        assert_eq!(CompactSize::validate_encoding(&[0xFC, 0xAA]), Ok(1));
+        assert_eq!(CompactSize::validate_encoding(&[0xFD, 0xFD, 0x00]), Ok(3));
+        assert_eq!(
+            CompactSize::validate_encoding(&[0xFE, 0x00, 0x00, 0x01, 0x00]),
+            Ok(5)
+        );
+        assert_eq!(
+            CompactSize::validate_encoding(&[0xFF, 0, 0, 0, 0, 0x01, 0, 0, 0]),
+            Ok(9)
+        );
+
+        for bytes in [
+            vec![0xFD, 0x10, 0x00],
+            vec![0xFE, 0xFF, 0xFF, 0x00, 0x00],
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0],
+        ] {
+            assert_eq!(
+                CompactSize::validate_encoding(&bytes),
+                Err(BitcoinError::NonCanonicalVarInt { offset: 0 })
+            );
+        }
+        assert!(matches!(
+            CompactSize::validate_encoding(&[0xFD, 0x10]),
+            Err(BitcoinError::InsufficientBytes { .. })
+        ));
+    }
+
+    #[test]
+    fn test_is_consolidation() {
+        let inputs = |n: u8| {
+            (0..n)
+                .map(|i| {
+                    TransactionInput::new(OutPoint::new(dummy_txid(i), 0), Script::new(vec![]), 0)
+                })
+                .collect::<Vec<_>>()
+        };
+        let output = || TransactionOutput::new(1_000, Script::new(vec![0x51]));
+
+        assert!(
+            BitcoinTransaction::with_outputs(2, inputs(3), vec![output()], 0).is_consolidation()
+        );
+        assert!(
+            !BitcoinTransaction::with_outputs(2, inputs(2), vec![output()], 0).is_consolidation()
+        );
+        assert!(
+            !BitcoinTransaction::with_outputs(2, inputs(9), vec![output(), output()], 0)
+                .is_consolidation()
+        );
+        assert!(!BitcoinTransaction::new(2, inputs(5), 0).is_consolidation());
+    }
+
+    // Signed transaction from the BIP-143 native P2WPKH example: input 0 is
+    // a legacy P2PK spend and input 1 a P2WPKH spend.
+    const BIP143_P2WPKH_TX_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    #[test]
+    fn test_segwit_tx_roundtrip() {
+        let bytes = hex::decode(BIP143_P2WPKH_TX_HEX).unwrap();
+        let (tx, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert!(tx.is_segwit());
+        assert_eq!(tx.inputs.len(), 2);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.witnesses.len(), 2);
+        assert!(tx.witnesses[0].is_empty());
+        assert_eq!(tx.witnesses[1].len(), 2);
+        assert_eq!(tx.witnesses[1][0].len(), 71);
+        assert_eq!(tx.witnesses[1][1].len(), 33);
+        assert_eq!(tx.outputs[0].value, 112_340_000);
+        assert_eq!(tx.lock_time, 17);
+        assert_eq!(tx.to_bytes(), bytes);
+
+        let legacy = tx.to_legacy_bytes();
+        assert_eq!(legacy.len(), bytes.len() - 2 - 1 - 1 - 1 - 71 - 1 - 33);
+        let (stripped, _) = BitcoinTransaction::from_bytes(&legacy).unwrap();
+        assert!(!stripped.is_segwit());
+        assert!(stripped.witnesses.is_empty());
+        assert_eq!(stripped.txid(), tx.txid());
+    }
+
+    #[test]
+    fn test_segwit_serialization_layout() {
+        let mut tx = BitcoinTransaction::with_outputs(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![]),
+                0xFFFFFFFD,
+            )],
+            vec![TransactionOutput::new(1_000, Script::new(vec![0x51]))],
+            0,
+        );
+        assert!(!tx.is_segwit());
+        let legacy = tx.to_bytes();
+
+        tx.witnesses = vec![Witness::new(vec![vec![0xAA; 3], vec![]])];
+        assert!(tx.is_segwit());
+        let bytes = tx.to_bytes();
+        assert_eq!(bytes[4..6], [0x00, 0x01]);
+        assert_eq!(
+            bytes[bytes.len() - 10..bytes.len() - 4],
+            [0x02, 0x03, 0xAA, 0xAA, 0xAA, 0x00]
+        );
+        assert_eq!(bytes.len(), legacy.len() + 2 + 6);
+        assert_eq!(BitcoinTransaction::from_bytes(&bytes).unwrap().0, tx);
+        assert_eq!(tx.chunks().collect::<Vec<_>>().concat(), bytes);
+
+        let hex_only: String = tx
+            .to_annotated_hex()
+            .lines()
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(hex_only, hex::encode(&bytes));
+
+        tx.witnesses = vec![Witness::default()];
+        let mut empty_witness = tx.to_bytes();
+        assert_eq!(empty_witness, legacy);
+        empty_witness.splice(4..4, [0x00, 0x01]);
+        let lock_time_pos = empty_witness.len() - 4;
+        empty_witness.insert(lock_time_pos, 0x00);
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&empty_witness),
+            Err(BitcoinError::InvalidFormat {
+                offset: lock_time_pos,
+                reason: "superfluous witness record",
+            })
+        );
+    }
+
+    #[test]
+    fn test_prevout_script_for_input() {
+        let prev_tx = BitcoinTransaction::with_outputs(
+            1,
+            vec![],
+            vec![
+                TransactionOutput::new(1_000, Script::new(vec![0x51])),
+                TransactionOutput::new(2_000, Script::new(vec![0x52])),
+            ],
+            0,
+        );
+        let tx = BitcoinTransaction::new(
+            2,
+            vec![
+                TransactionInput::new(OutPoint::new(prev_tx.txid().0, 1), Script::new(vec![]), 0),
+                TransactionInput::new(OutPoint::new(prev_tx.txid().0, 5), Script::new(vec![]), 0),
+            ],
+            0,
+        );
+        assert_eq!(
+            tx.prevout_script_for_input(0, &prev_tx),
+            Some(Script::new(vec![0x52]))
+        );
+        assert_eq!(tx.prevout_script_for_input(1, &prev_tx), None);
+        assert_eq!(tx.prevout_script_for_input(2, &prev_tx), None);
+
+        // An output index that exists in an unrelated transaction is not used.
+        let mut unrelated = prev_tx.clone();
+        unrelated.lock_time = 1;
+        assert_eq!(tx.prevout_script_for_input(0, &unrelated), None);
+    }
+
+    #[test]
+    fn test_error_offsets_on_truncation() {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        // (truncation point, field that runs short, its start, its width)
+        let cases = [
+            (2, "version", 0, 4),
+            (4, "CompactSize", 4, 1), // input count
+            (100, "script", 42, 72),  // scriptSig body
+            (116, "sequence", 114, 4),
+            (118, "CompactSize", 118, 1), // output count
+            (190, "script", 128, 67),     // output 0 scriptPubKey body
+            (200, "output value", 195, 8),
+            (bytes.len() - 1, "lock_time", bytes.len() - 4, 4),
+        ];
+        for (truncate_at, field, offset, needed) in cases {
+            let err = BitcoinTransaction::from_bytes(&bytes[..truncate_at]).unwrap_err();
+            assert_eq!(
+                err,
+                BitcoinError::InsufficientBytes {
+                    field,
+                    offset,
+                    needed,
+                    available: truncate_at - offset,
+                },
+                "truncated at {}",
+                truncate_at
+            );
+            assert_eq!(err.offset(), Some(offset));
+        }
+
+        // A count that cannot fit in what is left is blamed on the count itself.
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&bytes[..20]),
+            Err(BitcoinError::InvalidFormat {
+                offset: 4,
+                reason: "count exceeds remaining bytes",
+            })
+        );
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&bytes[..125]),
+            Err(BitcoinError::InvalidFormat {
+                offset: 118,
+                reason: "count exceeds remaining bytes",
+            })
+        );
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(tx.to_hex(), BLOCK_170_TX_HEX);
+
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(segwit.to_hex(), BIP143_P2WPKH_TX_HEX);
+
+        let with_trailing = format!("{}00", BLOCK_170_TX_HEX);
+        assert_eq!(
+            BitcoinTransaction::from_hex(&with_trailing),
+            Err(BitcoinError::InvalidFormat {
+                offset: BLOCK_170_TX_HEX.len() / 2,
+                reason: "trailing bytes",
+            })
+        );
+        assert!(matches!(
+            BitcoinTransaction::from_hex("0100000zz"),
+            Err(BitcoinError::InvalidFormat { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_equal_value_output_count() {
+        let input = TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(vec![]), 0);
+        let outputs = [100_000, 37_512, 100_000, 100_000, 8_000, 100_000]
+            .into_iter()
+            .map(|value| TransactionOutput::new(value, Script::new(vec![0x51])))
+            .collect();
+        let tx = BitcoinTransaction::with_outputs(2, vec![input], outputs, 0);
+        assert_eq!(tx.equal_value_output_count(), 4);
+
+        assert_eq!(
+            BitcoinTransaction::new(2, vec![], 0).equal_value_output_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_decode_exact() {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        let tx = BitcoinTransaction::decode_exact(&bytes).unwrap();
+        assert_eq!(tx.to_bytes(), bytes);
+
+        let mut padded = bytes.clone();
+        padded.push(0x00);
+        assert_eq!(
+            BitcoinTransaction::decode_exact(&padded),
+            Err(BitcoinError::InvalidFormat {
+                offset: bytes.len(),
+                reason: "trailing bytes",
+            })
+        );
+
+        assert_eq!(
+            CompactSize::decode_exact(&[0xFD, 0x00, 0x01]),
+            Ok(CompactSize::new(256))
+        );
+        assert!(CompactSize::decode_exact(&[0x01, 0x00]).is_err());
+
+        let outpoint = OutPoint::new([7; 32], 3);
+        assert_eq!(
+            OutPoint::decode_exact(&outpoint.to_bytes()),
+            Ok(outpoint.clone())
+        );
+        let mut extra = outpoint.to_bytes();
+        extra.push(0xFF);
+        assert!(matches!(
+            OutPoint::decode_exact(&extra),
+            Err(BitcoinError::InvalidFormat { offset: 36, .. })
+        ));
+
+        let output = TransactionOutput::new(5_000, Script::new(vec![0x51]));
+        assert_eq!(
+            TransactionOutput::decode_exact(&output.to_bytes()),
+            Ok(output)
+        );
+        assert!(Script::decode_exact(&[0x01, 0x51, 0x51]).is_err());
+    }
+
+    #[test]
+    fn test_has_timelock_opcode() {
+        // <locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP <pubkey> OP_CHECKSIG
+        let mut cltv = vec![0x03, 0x40, 0x0D, 0x03, 0xB1, 0x75, 0x21];
+        cltv.extend_from_slice(&[0x02; 33]);
+        cltv.push(0xAC);
+        assert!(Script::new(cltv).has_timelock_opcode());
+
+        // <144> OP_CHECKSEQUENCEVERIFY
+        assert!(Script::new(vec![0x02, 0x90, 0x00, 0xB2]).has_timelock_opcode());
+
+        // 0xB1 as push data is not the opcode.
+        assert!(!Script::new(vec![0x02, 0xB1, 0xB2, 0x87]).has_timelock_opcode());
+        assert!(!Script::new(vec![0x51]).has_timelock_opcode());
+    }
+
+    #[test]
+    fn test_serialized_size_weight_vsize() {
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let large = BitcoinTransaction::with_outputs(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0xAA; 300]),
+                0,
+            )],
+            vec![TransactionOutput::new(1, Script::new(vec![0x6A; 70_000]))],
+            0,
+        );
+        for tx in [
+            &legacy,
+            &segwit,
+            &large,
+            &BitcoinTransaction::new(1, vec![], 0),
+        ] {
+            assert_eq!(tx.serialized_size(), tx.to_bytes().len());
+        }
+
+        assert_eq!(legacy.weight(), 4 * legacy.serialized_size());
+        assert_eq!(legacy.vsize(), legacy.serialized_size());
+
+        assert_eq!(segwit.serialized_size(), 343);
+        assert_eq!(segwit.base_size(), 233);
+        assert_eq!(segwit.weight(), 1042);
+        assert_eq!(segwit.vsize(), 261);
+        assert_eq!(segwit.total_size(), segwit.to_bytes().len());
+        assert_eq!(legacy.total_size(), legacy.base_size());
+    }
+
+    #[test]
+    fn test_fee() {
+        // Amounts from the BIP-143 native P2WPKH example.
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let spent = [
+            TransactionOutput::new(625_000_000, Script::new(vec![0x21; 35])),
+            TransactionOutput::new(600_000_000, Script::new(vec![0x00, 0x14])),
+        ];
+        assert_eq!(
+            segwit.fee(&spent),
+            Ok(1_225_000_000 - 112_340_000 - 223_450_000)
+        );
+
+        assert_eq!(
+            segwit.fee(&spent[..1]),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "spent output count does not match input count",
+            })
+        );
+        let short = [
+            TransactionOutput::new(100_000_000, Script::default()),
+            TransactionOutput::new(100_000_000, Script::default()),
+        ];
+        assert_eq!(
+            segwit.fee(&short),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "outputs exceed inputs",
+            })
+        );
+        let huge = [
+            TransactionOutput::new(u64::MAX, Script::default()),
+            TransactionOutput::new(1, Script::default()),
+        ];
+        assert_eq!(
+            segwit.fee(&huge),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "value overflow",
+            })
+        );
+    }
+
+    #[test]
+    fn test_extract_timelock() {
+        // OP_IF OP_SHA256 <hash> OP_EQUALVERIFY <pk> OP_ELSE <500000> OP_CLTV
+        // OP_DROP <pk> OP_ENDIF OP_CHECKSIG
+        let mut htlc = vec![0x63, 0xA8, 0x20];
+        htlc.extend_from_slice(&[0x11; 32]);
+        htlc.extend_from_slice(&[0x88, 0x21]);
+        htlc.extend_from_slice(&[0x02; 33]);
+        htlc.extend_from_slice(&[0x67, 0x03, 0x20, 0xA1, 0x07, 0xB1, 0x75, 0x21]);
+        htlc.extend_from_slice(&[0x03; 33]);
+        htlc.extend_from_slice(&[0x68, 0xAC]);
+        assert_eq!(Script::new(htlc).extract_timelock(), Some(500_000));
+
+        assert_eq!(
+            Script::new(vec![0x02, 0x90, 0x00, 0xB2]).extract_timelock(),
+            Some(144)
+        );
+        assert_eq!(Script::new(vec![0x60, 0xB2]).extract_timelock(), Some(16));
+        assert_eq!(
+            Script::new(vec![0x05, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0xB1]).extract_timelock(),
+            Some(0xFFFF_FFFF)
+        );
+        assert_eq!(
+            Script::new(vec![0x01, 0x81, 0xB1]).extract_timelock(),
+            Some(-1)
+        );
+        assert_eq!(Script::new(vec![0xB1]).extract_timelock(), None);
+        assert_eq!(Script::new(vec![0x51, 0xAC]).extract_timelock(), None);
+    }
+
+    #[test]
+    fn test_transaction_builder() {
+        let expected = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let input = &expected.inputs[0];
+        let mut builder = TransactionBuilder::new(expected.version).add_input(
+            input.previous_output.clone(),
+            input.script_sig.clone(),
+            input.sequence,
+        );
+        for output in &expected.outputs {
+            builder = builder.add_output(output.value, output.script_pubkey.clone());
+        }
+        let built = builder.lock_time(expected.lock_time).build().unwrap();
+        assert_eq!(built, expected);
+        assert_eq!(built.to_bytes(), expected.to_bytes());
+
+        let outpoint = OutPoint::new(dummy_txid(1), 2);
+        let defaults = TransactionBuilder::new(2)
+            .add_input_default_sequence(outpoint.clone(), Script::new(vec![0x51]))
+            .add_output(1_000, Script::new(vec![0x6A]))
+            .build()
+            .unwrap();
+        let by_hand = BitcoinTransaction::with_outputs(
+            2,
+            vec![TransactionInput::new(
+                outpoint,
+                Script::new(vec![0x51]),
+                0xFFFFFFFF,
+            )],
+            vec![TransactionOutput::new(1_000, Script::new(vec![0x6A]))],
+            0,
+        );
+        assert_eq!(defaults.to_bytes(), by_hand.to_bytes());
+    }
+
+    #[test]
+    fn test_witness_weight_fraction() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        // Marker, flag and both witness stacks.
+        assert_eq!(segwit.witness_weight(), 110);
+        assert_eq!(segwit.witness_weight_fraction(), 110.0 / 1042.0);
+
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(legacy.witness_weight(), 0);
+        assert_eq!(legacy.witness_weight_fraction(), 0.0);
+    }
+
+    #[test]
+    fn test_build_unsigned() {
+        let outpoints = vec![
+            OutPoint::new(dummy_txid(1), 0),
+            OutPoint::new(dummy_txid(2), 5),
+        ];
+        let tx = BitcoinTransaction::build_unsigned(
+            outpoints.clone(),
+            vec![
+                (50_000, Script::new(vec![0x51])),
+                (1_200, Script::new(vec![0x52])),
+            ],
+            800_000,
+        );
+        assert_eq!(tx.version, 2);
+        assert_eq!(tx.lock_time, 800_000);
+        assert!(!tx.is_segwit());
+        for (input, outpoint) in tx.inputs.iter().zip(&outpoints) {
+            assert_eq!(&input.previous_output, outpoint);
+            assert!(input.script_sig.is_empty());
+            assert_eq!(input.sequence, 0xFFFFFFFD);
+        }
+        assert_eq!(
+            tx.outputs[1],
+            TransactionOutput::new(1_200, Script::new(vec![0x52]))
+        );
+    }
+
+    #[test]
+    fn test_all_inputs_rbf() {
+        let tx = |sequences: &[u32]| {
+            let inputs = sequences
+                .iter()
+                .map(|&sequence| {
+                    TransactionInput::new(
+                        OutPoint::new(dummy_txid(1), 0),
+                        Script::default(),
+                        sequence,
+                    )
+                })
+                .collect();
+            BitcoinTransaction::new(2, inputs, 0)
+        };
+        assert!(tx(&[0xFFFFFFFD, 0]).all_inputs_rbf());
+        assert!(!tx(&[0xFFFFFFFD, 0xFFFFFFFE]).all_inputs_rbf());
+        assert!(!tx(&[0xFFFFFFFF]).all_inputs_rbf());
+        assert!(!tx(&[]).all_inputs_rbf());
+    }
+
+    #[test]
+    fn test_dust_output_indices() {
+        let mut p2pkh = vec![0x76, 0xA9, 0x14];
+        p2pkh.extend_from_slice(&[0x11; 20]);
+        p2pkh.extend_from_slice(&[0x88, 0xAC]);
+        let mut p2wpkh = vec![0x00, 0x14];
+        p2wpkh.extend_from_slice(&[0x22; 20]);
+        let op_return = Script::new(vec![0x6A, 0x01, 0xFF]);
+
+        // Default dust relay fee of 3000 sat/kvB: thresholds 546 and 294.
+        assert!(!TransactionOutput::new(546, Script::new(p2pkh.clone())).is_dust(3_000));
+        assert!(TransactionOutput::new(545, Script::new(p2pkh.clone())).is_dust(3_000));
+        assert!(!TransactionOutput::new(294, Script::new(p2wpkh.clone())).is_dust(3_000));
+        assert!(TransactionOutput::new(293, Script::new(p2wpkh.clone())).is_dust(3_000));
+        assert!(!TransactionOutput::new(0, op_return.clone()).is_dust(3_000));
+
+        let outputs = vec![
+            TransactionOutput::new(10_000, Script::new(p2pkh.clone())),
+            TransactionOutput::new(500, Script::new(p2pkh)),
+            TransactionOutput::new(0, op_return),
+            TransactionOutput::new(100, Script::new(p2wpkh)),
+        ];
+        let tx = BitcoinTransaction::with_outputs(2, vec![], outputs, 0);
+        assert_eq!(tx.dust_output_indices(3_000), vec![1, 3]);
+        assert!(tx.dust_output_indices(0).is_empty());
+    }
+
+    #[test]
+    fn test_is_burn() {
+        let p2pkh = |hash: &str| {
+            let mut script = vec![0x76, 0xA9, 0x14];
+            script.extend(hex::decode(hash).unwrap());
+            script.extend_from_slice(&[0x88, 0xAC]);
+            Script::new(script)
+        };
+        assert!(p2pkh("759d6677091e973b9e9d99f19c68fbf43e3f05f9").is_burn());
+        assert!(!p2pkh("62e907b15cbf27d5425399ebf6f0fb50ebb88f18").is_burn());
+        assert!(Script::new(vec![0x6A]).is_burn());
+        assert!(Script::new(vec![0x6A, 0x04, 0xDE, 0xAD, 0xBE, 0xEF]).is_burn());
+        assert!(!Script::new(vec![0x51]).is_burn());
+        assert!(!Script::default().is_burn());
+    }
+
+    #[test]
+    fn test_segwit_savings_estimate() {
+        let mut script_sig = vec![0x48];
+        script_sig.extend_from_slice(&[0x30; 72]);
+        script_sig.push(0x21);
+        script_sig.extend_from_slice(&[0x02; 33]);
+        let mut p2pkh = vec![0x76, 0xA9, 0x14];
+        p2pkh.extend_from_slice(&[0x11; 20]);
+        p2pkh.extend_from_slice(&[0x88, 0xAC]);
+        let tx = BitcoinTransaction::with_outputs(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(script_sig),
+                0xFFFFFFFF,
+            )],
+            vec![TransactionOutput::new(10_000, Script::new(p2pkh))],
+            0,
+        );
+        // 192 vbytes as p2pkh against 113 as p2wpkh.
+        assert_eq!(tx.vsize(), 192);
+        assert_eq!(tx.segwit_savings_estimate(), 79);
+
+        // Block 170 spends a P2PK output, which has no p2wpkh equivalent.
+        let p2pk = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(p2pk.segwit_savings_estimate(), 0);
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(segwit.segwit_savings_estimate(), 0);
+    }
+
+    #[test]
+    fn test_verify_wtxid() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let wtxid = segwit.wtxid();
+        assert_eq!(
+            wtxid.to_rpc_hex(),
+            "c36c38370907df2324d9ce9d149d191192f338b37665a82e78e76a12c909b762"
+        );
+        assert!(segwit.verify_wtxid(&wtxid));
+        assert_ne!(wtxid.0, segwit.txid().0);
+        assert!(!segwit.verify_wtxid(&Wtxid(segwit.txid().0)));
+
+        // Without witnesses the wtxid is the txid.
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert!(legacy.verify_wtxid(&Wtxid(legacy.txid().0)));
+    }
+
+    #[test]
+    fn test_verify_txid() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let txid = Txid(
+            hex::decode("169e1e83e930853391bc6f35f605c6754cfead57cf8387639d3b4096c54f18f4")
+                .unwrap()
+                .try_into()
+                .unwrap(),
+        );
+        assert!(tx.verify_txid(&txid));
+        assert!(!tx.verify_txid(&Txid(dummy_txid(1))));
+
+        // A segwit transaction's txid ignores the witness, unlike its wtxid.
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert!(segwit.verify_txid(&segwit.txid()));
+        assert!(!segwit.verify_txid(&Txid(segwit.wtxid().0)));
+    }
+
+    #[test]
+    fn test_batch_inputs() {
+        let inputs: Vec<_> = (0..5)
+            .map(|i| TransactionInput::new(OutPoint::new(dummy_txid(i), 0), Script::default(), 0))
+            .collect();
+        let tx = BitcoinTransaction::new(2, inputs.clone(), 0);
+
+        let batches = tx.batch_inputs(2);
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[0], inputs[..2]);
+        assert_eq!(batches[2], inputs[4..]);
+        assert_eq!(tx.batch_inputs(10), vec![inputs]);
+        assert!(
+            BitcoinTransaction::new(2, vec![], 0)
+                .batch_inputs(3)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_with_sequence() {
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let bumped = tx.with_sequence(1, 0xFFFFFFFD).unwrap();
+        assert_eq!(bumped.inputs[1].sequence, 0xFFFFFFFD);
+        assert_eq!(bumped.inputs[0], tx.inputs[0]);
+        assert_eq!(bumped.witnesses, tx.witnesses);
+        assert_eq!(tx.inputs[1].sequence, 0xFFFFFFFF);
+
+        assert_eq!(
+            tx.with_sequence(2, 0),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "input index out of range",
+            })
+        );
+    }
+
+    #[test]
+    fn test_is_simple_payment() {
+        let outputs = |n: u64| {
+            (0..n)
+                .map(|i| TransactionOutput::new(1_000 * (i + 1), Script::new(vec![0x51])))
+                .collect::<Vec<_>>()
+        };
+        assert!(BitcoinTransaction::with_outputs(2, vec![], outputs(2), 0).is_simple_payment());
+        assert!(!BitcoinTransaction::with_outputs(2, vec![], outputs(1), 0).is_simple_payment());
+        assert!(!BitcoinTransaction::with_outputs(2, vec![], outputs(3), 0).is_simple_payment());
+        assert!(
+            BitcoinTransaction::from_hex(BLOCK_170_TX_HEX)
+                .unwrap()
+                .is_simple_payment()
+        );
+    }
+
+    #[test]
+    fn test_find_output() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let change = tx.outputs[1].clone();
+        assert_eq!(tx.find_output(&change.script_pubkey, change.value), Some(1));
+        assert_eq!(
+            tx.find_output(&change.script_pubkey, change.value + 1),
+            None
+        );
+        assert_eq!(
+            tx.find_output(&tx.outputs[0].script_pubkey, change.value),
+            None
+        );
+    }
+
+    #[test]
+    fn test_package_fee_rate() {
+        let parent = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let child = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let vsize = parent.vsize() + child.vsize();
+        let rate = package_fee_rate(&[parent.clone(), child], &[261, 10_000]).unwrap();
+        assert_eq!(rate, 10_261.0 / vsize as f64);
+
+        assert!(matches!(
+            package_fee_rate(std::slice::from_ref(&parent), &[1, 2]),
+            Err(BitcoinError::InvalidFormat { .. })
+        ));
+        assert!(matches!(
+            package_fee_rate(&[], &[]),
+            Err(BitcoinError::InvalidFormat { .. })
+        ));
+        assert_eq!(
+            package_fee_rate(&[parent.clone(), parent], &[u64::MAX, 1]),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "value overflow",
+            })
+        );
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(
+            tx.summary_line(),
+            "txid=f4184fc5 in=1 out=2 vsize=275 rbf=no"
+        );
+
+        let bumped = tx.with_sequence(0, 0xFFFFFFFD).unwrap();
+        assert!(bumped.summary_line().ends_with("rbf=yes"));
+    }
+
+    #[test]
+    fn test_is_pinning_risk() {
+        let large = BitcoinTransaction::with_outputs(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x51; 20_000]),
+                0xFFFFFFFD,
+            )],
+            vec![TransactionOutput::new(1_000, Script::new(vec![0x51]))],
+            0,
+        );
+        let vsize = large.vsize() as u64;
+        assert!(large.is_pinning_risk(vsize, 2.0));
+        assert!(!large.is_pinning_risk(vsize * 2, 2.0));
+
+        let small = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert!(!small.is_pinning_risk(0, 2.0));
+    }
+
+    #[test]
+    fn test_transaction_sha256() {
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(
+            hex::encode(legacy.sha256()),
+            "240cf324ec3cf59609733e2a45e1408673306be8dcd4caf3067aa9355a0269e3"
+        );
+        assert_ne!(legacy.sha256(), legacy.txid().0);
+
+        // Witness data is excluded, as for the txid.
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(
+            hex::encode(segwit.sha256()),
+            "a15d5b16df80689b194d238d2419c459f9e165a6374ecfa8ffae1570293cf305"
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_change() {
+        let p2wpkh = |fill: u8| {
+            let mut script = vec![0x00, 0x14];
+            script.extend_from_slice(&[fill; 20]);
+            Script::new(script)
+        };
+        let mut p2pkh = vec![0x76, 0xA9, 0x14];
+        p2pkh.extend_from_slice(&[0x11; 20]);
+        p2pkh.extend_from_slice(&[0x88, 0xAC]);
+        let tx = |outputs: Vec<(u64, Script)>| {
+            let outputs = outputs
+                .into_iter()
+                .map(|(value, script)| TransactionOutput::new(value, script))
+                .collect();
+            BitcoinTransaction::with_outputs(2, vec![], outputs, 0)
+        };
+
+        assert!(tx(vec![(1_234_567, p2wpkh(1)), (2_345_678, p2wpkh(2))]).ambiguous_change());
+        assert!(tx(vec![(1_000_000, p2wpkh(1)), (500_000, p2wpkh(2))]).ambiguous_change());
+        // The round payment gives the change away.
+        assert!(!tx(vec![(1_000_000, p2wpkh(1)), (2_345_678, p2wpkh(2))]).ambiguous_change());
+        // So does a change output of a different type.
+        assert!(
+            !tx(vec![
+                (1_234_567, p2wpkh(1)),
+                (2_345_678, Script::new(p2pkh))
+            ])
+            .ambiguous_change()
+        );
+        // Same length and leading opcode, but not the same script type.
+        let mut p2wsh = vec![0x00, 0x20];
+        p2wsh.extend_from_slice(&[0x22; 32]);
+        let mut lookalike = vec![0x00, 0x01, 0x22];
+        lookalike.extend_from_slice(&[0x61; 31]);
+        assert!(
+            !tx(vec![
+                (1_234_567, Script::new(p2wsh)),
+                (2_345_678, Script::new(lookalike))
+            ])
+            .ambiguous_change()
+        );
+        assert!(!tx(vec![(1_234_567, p2wpkh(1))]).ambiguous_change());
+    }
+
+    #[test]
+    fn test_to_p2p_message() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let mainnet = [0xF9, 0xBE, 0xB4, 0xD9];
+        let message = tx.to_p2p_message(mainnet);
+        let payload = tx.to_bytes();
+
+        assert_eq!(message[..4], mainnet);
+        assert_eq!(&message[4..16], b"tx\0\0\0\0\0\0\0\0\0\0");
+        assert_eq!(message[16..20], (payload.len() as u32).to_le_bytes());
+        // For a transaction the checksum is the leading bytes of its txid.
+        assert_eq!(hex::encode(&message[20..24]), "169e1e83");
+        assert_eq!(message[20..24], tx.txid().0[..4]);
+        assert_eq!(message[24..], payload[..]);
+    }
+
+    #[test]
+    fn test_parse_p2p_tx_message() {
+        let mainnet = [0xF9, 0xBE, 0xB4, 0xD9];
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let message = tx.to_p2p_message(mainnet);
+        assert_eq!(parse_p2p_tx_message(&message, mainnet), Ok(tx));
+
+        let reason = |bytes: &[u8], magic| match parse_p2p_tx_message(bytes, magic) {
+            Err(BitcoinError::InvalidFormat { reason, .. }) => reason,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(
+            reason(&message, [0x0B, 0x11, 0x09, 0x07]),
+            "unexpected network magic"
+        );
+
+        let mut wrong_command = message.clone();
+        wrong_command[4] = b'b';
+        assert_eq!(reason(&wrong_command, mainnet), "not a tx message");
+
+        let mut truncated = message.clone();
+        truncated.pop();
+        assert!(matches!(
+            parse_p2p_tx_message(&truncated, mainnet),
+            Err(BitcoinError::InsufficientBytes {
+                field: "payload",
+                ..
+            })
+        ));
+        let mut extended = message.clone();
+        extended.push(0x00);
+        assert_eq!(reason(&extended, mainnet), "trailing bytes");
+
+        let mut corrupted = message.clone();
+        *corrupted.last_mut().unwrap() ^= 0xFF;
+        assert_eq!(reason(&corrupted, mainnet), "checksum mismatch");
+
+        assert!(matches!(
+            parse_p2p_tx_message(&message[..10], mainnet),
+            Err(BitcoinError::InsufficientBytes { .. })
+        ));
+    }
+
+    #[test]
+    fn test_weighted_size() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(segwit.weighted_size(4), segwit.weight());
+        assert_eq!(segwit.weighted_size(8), 8 * 233 + 110);
+        assert_eq!(segwit.weighted_size(1), segwit.serialized_size());
+
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(legacy.weighted_size(4), legacy.weight());
+    }
+
+    #[test]
+    fn test_empty_scriptsig_inputs() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(segwit.empty_scriptsig_inputs(), vec![1]);
+
+        let unsigned = BitcoinTransaction::build_unsigned(
+            vec![
+                OutPoint::new(dummy_txid(1), 0),
+                OutPoint::new(dummy_txid(2), 0),
+            ],
+            vec![],
+            0,
+        );
+        assert_eq!(unsigned.empty_scriptsig_inputs(), vec![0, 1]);
+
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert!(legacy.empty_scriptsig_inputs().is_empty());
+    }
+
+    #[test]
+    fn test_finalize() {
+        for hex in [BLOCK_170_TX_HEX, BIP143_P2WPKH_TX_HEX] {
+            let tx = BitcoinTransaction::from_hex(hex).unwrap();
+            let (bytes, txid) = tx.finalize();
+            assert_eq!(bytes, tx.to_bytes());
+            assert_eq!(txid, tx.txid());
+        }
+        let (_, txid) = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX)
+            .unwrap()
+            .finalize();
+        assert_eq!(
+            txid.to_rpc_hex(),
+            "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609"
+        );
+    }
+
+    #[test]
+    fn test_spends_coinbase_at() {
+        let inputs = (0..2)
+            .map(|i| TransactionInput::new(OutPoint::new(dummy_txid(i), 0), Script::default(), 0))
+            .collect();
+        let tx = BitcoinTransaction::new(2, inputs, 0);
+
+        // A coinbase from block 1000 becomes spendable in block 1100.
+        assert_eq!(
+            tx.spends_coinbase_at(&[false, true], &[5, 1_000], 1_099),
+            Ok(true)
+        );
+        assert_eq!(
+            tx.spends_coinbase_at(&[false, true], &[5, 1_000], 1_100),
+            Ok(false)
+        );
+        assert_eq!(
+            tx.spends_coinbase_at(&[false, false], &[1_050, 1_050], 1_060),
+            Ok(false)
+        );
+
+        assert!(matches!(
+            tx.spends_coinbase_at(&[true], &[1_000, 1_000], 1_100),
+            Err(BitcoinError::InvalidFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn test_content_fingerprint() {
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let mut reordered = tx.clone();
+        reordered.inputs.reverse();
+        reordered.witnesses.reverse();
+        reordered.outputs.reverse();
+        assert_ne!(reordered.txid(), tx.txid());
+        assert_eq!(reordered.content_fingerprint(), tx.content_fingerprint());
+
+        let mut changed = tx.clone();
+        changed.outputs[0].value += 1;
+        assert_ne!(changed.content_fingerprint(), tx.content_fingerprint());
+        let mut relocked = tx.clone();
+        relocked.lock_time += 1;
+        assert_ne!(relocked.content_fingerprint(), tx.content_fingerprint());
+    }
+
+    #[test]
+    fn test_locktime_is_effective() {
+        let tx = |sequence: u32, lock_time: u32| {
+            let input =
+                TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::default(), sequence);
+            BitcoinTransaction::new(2, vec![input], lock_time)
+        };
+        assert!(tx(0xFFFFFFFE, 800_000).locktime_is_effective());
+        assert!(!tx(0xFFFFFFFF, 800_000).locktime_is_effective());
+        assert!(!tx(0xFFFFFFFE, 0).locktime_is_effective());
+        // BIP-143 example: lock_time 17 with input 0 at sequence 0xffffffee.
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert!(segwit.locktime_is_effective());
+    }
+
+    #[test]
+    fn test_witness_to_scriptsig_stack() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let stack = segwit.witness_to_scriptsig_stack(1);
+        assert_eq!(stack.len(), 2);
+
+        // The p2pkh scriptSig pushing the same items yields the same stack.
+        let mut script_sig = Vec::new();
+        for item in &stack {
+            script_sig.push(item.len() as u8);
+            script_sig.extend_from_slice(item);
+        }
+        let mut legacy = segwit.clone();
+        legacy.witnesses.clear();
+        legacy.inputs.truncate(1);
+        legacy.outputs.clear();
+        legacy.inputs[0].script_sig = Script::new(script_sig);
+        assert_eq!(legacy.all_data_pushes(), stack);
+
+        assert!(segwit.witness_to_scriptsig_stack(0).is_empty());
+        assert!(segwit.witness_to_scriptsig_stack(5).is_empty());
+    }
+
+    #[test]
+    fn test_concat_and_split_transactions() {
+        let txs = vec![
+            BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap(),
+            BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap(),
+            BitcoinTransaction::new(2, vec![], 0),
+        ];
+        let bytes = concat_transactions(&txs);
+        assert_eq!(
+            bytes.len(),
+            txs.iter()
+                .map(BitcoinTransaction::serialized_size)
+                .sum::<usize>()
+        );
+        assert_eq!(split_transactions(&bytes, 3), Ok(txs));
+
+        let first_len = BLOCK_170_TX_HEX.len() / 2;
+        assert_eq!(
+            split_transactions(&bytes, 1),
+            Err(BitcoinError::InvalidFormat {
+                offset: first_len,
+                reason: "trailing bytes",
+            })
+        );
+        assert!(matches!(
+            split_transactions(&bytes, 4),
+            Err(BitcoinError::InsufficientBytes { offset, .. }) if offset == bytes.len()
+        ));
+        assert_eq!(split_transactions(&[], 0), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_looks_like_coinjoin() {
+        let inputs = |n: u8| {
+            (0..n)
+                .map(|i| {
+                    TransactionInput::new(OutPoint::new(dummy_txid(i), 0), Script::default(), 0)
+                })
+                .collect::<Vec<_>>()
+        };
+        let outputs = |values: &[u64]| {
+            values
+                .iter()
+                .map(|&value| TransactionOutput::new(value, Script::new(vec![0x51])))
+                .collect::<Vec<_>>()
+        };
+        let mixed = [1_000_000, 1_000_000, 1_000_000, 1_000_000, 3_141, 27_182];
+        let coinjoin = BitcoinTransaction::with_outputs(2, inputs(5), outputs(&mixed), 0);
+        assert!(coinjoin.looks_like_coinjoin(4));
+        assert!(!coinjoin.looks_like_coinjoin(5));
+
+        let few_inputs = BitcoinTransaction::with_outputs(2, inputs(2), outputs(&mixed), 0);
+        assert!(!few_inputs.looks_like_coinjoin(4));
+    }
+
+    #[test]
+    fn test_graph_edges() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let edges = segwit.graph_edges();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0], (segwit.inputs[0].previous_output.txid.clone(), 0));
+        assert_eq!(edges[1].1, 1);
+        assert_eq!(
+            edges[1].0.to_rpc_hex(),
+            "8ac60eb9575db5b2d987e29f301b5b819ea83a5c6579d282d189cc04b8e151ef"
+        );
+        assert!(
+            BitcoinTransaction::new(2, vec![], 0)
+                .graph_edges()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_witness_roundtrip() {
+        let witness = Witness::new(vec![vec![0x30; 71], vec![0x02; 33], vec![]]);
+        let bytes = witness.to_bytes();
+        assert_eq!(bytes.len(), witness.serialized_size());
+        assert_eq!(bytes[..2], [0x03, 71]);
+        assert_eq!(
+            Witness::from_bytes(&bytes),
+            Ok((witness.clone(), bytes.len()))
+        );
+        assert_eq!(Witness::decode_exact(&bytes), Ok(witness));
+
+        assert_eq!(Witness::from_bytes(&[0x00]), Ok((Witness::default(), 1)));
+        // Second item claims 5 bytes but only 2 follow.
+        assert_eq!(
+            Witness::from_bytes(&[0x02, 0x01, 0xAA, 0x05, 0xBB, 0xCC]),
+            Err(BitcoinError::InsufficientBytes {
+                field: "witness item",
+                offset: 4,
+                needed: 5,
+                available: 2,
+            })
+        );
+
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert!(tx.witnesses[0].is_empty());
+        assert_eq!(tx.witnesses[1].len(), 2);
+    }
+
+    #[test]
+    fn test_txid_wtxid_display() {
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(
+            tx.txid().to_string(),
+            "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609"
+        );
+        assert_eq!(
+            tx.wtxid().to_string(),
+            "c36c38370907df2324d9ce9d149d191192f338b37665a82e78e76a12c909b762"
+        );
+    }
+
+    #[test]
+    fn test_consensus_encode_decode() {
+        fn roundtrip<T: Encodable + Decodable + PartialEq + std::fmt::Debug>(
+            value: T,
+            bytes: &[u8],
+        ) {
+            let mut encoded = Vec::new();
+            assert_eq!(value.consensus_encode(&mut encoded).unwrap(), bytes.len());
+            assert_eq!(encoded, bytes);
+            let mut reader = bytes;
+            assert_eq!(T::consensus_decode(&mut reader).unwrap(), value);
+            assert!(reader.is_empty());
+        }
+
+        let size = CompactSize::new(70_000);
+        roundtrip(size.clone(), &size.to_bytes());
+        let outpoint = OutPoint::new(dummy_txid(3), 7);
+        roundtrip(outpoint.clone(), &outpoint.to_bytes());
+        let script = Script::new(vec![0x51; 300]);
+        roundtrip(script.clone(), &script.to_bytes());
+        let input = TransactionInput::new(outpoint, script, 0xFFFFFFFD);
+        roundtrip(input.clone(), &input.to_bytes());
+
+        // Two outputs, so the zero input count is not read as a segwit marker.
+        let output = TransactionOutput::new(5, Script::new(vec![0x6A]));
+        let no_inputs =
+            BitcoinTransaction::with_outputs(2, vec![], vec![output.clone(), output], 0);
+        for tx in [
+            BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap(),
+            BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap(),
+            BitcoinTransaction::new(1, vec![], 0),
+            no_inputs,
+        ] {
+            let bytes = tx.to_bytes();
+            roundtrip(tx, &bytes);
+        }
+    }
+
+    #[test]
+    fn test_consensus_decode_stream() {
+        let first = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let second = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let bytes = concat_transactions(&[first.clone(), second.clone()]);
+        let mut cursor = std::io::Cursor::new(&bytes);
+        assert_eq!(
+            BitcoinTransaction::consensus_decode(&mut cursor).unwrap(),
+            first
+        );
+        assert_eq!(
+            BitcoinTransaction::consensus_decode(&mut cursor).unwrap(),
+            second
+        );
+        assert_eq!(cursor.position() as usize, bytes.len());
+
+        let truncated = &bytes[..bytes.len() - 1];
+        let mut reader = &truncated[first.to_bytes().len()..];
+        assert_eq!(
+            BitcoinTransaction::consensus_decode(&mut reader)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+
+        let mut non_canonical: &[u8] = &[0xFD, 0x10, 0x00];
+        assert_eq!(
+            CompactSize::consensus_decode(&mut non_canonical)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        // A script length far beyond the input must not be trusted for allocation.
+        let mut huge: &[u8] = &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F, 0x51];
+        assert_eq!(
+            Script::consensus_decode(&mut huge).unwrap_err().kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_opcode_roundtrip() {
+        assert_eq!(Opcode::from_byte(0x76), Some(Opcode::Dup));
+        assert_eq!(Opcode::CheckSig.to_byte(), 0xAC);
+        assert_eq!(Opcode::TwoDrop.name(), "OP_2DROP");
+        assert_eq!(Opcode::from_byte(0x14), None);
+        assert_eq!(Opcode::from_byte(0xBB), None);
+        for byte in std::iter::once(0x00).chain(0x4C..=0xBA) {
+            let opcode = Opcode::from_byte(byte).unwrap();
+            assert_eq!(opcode.to_byte(), byte);
+            assert!(opcode.name().starts_with("OP_"));
+        }
+    }
+
+    #[test]
+    fn test_script_instructions_and_asm() {
+        let mut p2pkh = vec![0x76, 0xA9, 0x14];
+        p2pkh.extend_from_slice(&[0xAB; 20]);
+        p2pkh.extend_from_slice(&[0x88, 0xAC]);
+        let script = Script::new(p2pkh);
+        let instructions: Vec<_> = script.instructions().collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::Op(Opcode::Dup),
+                Instruction::Op(Opcode::Hash160),
+                Instruction::PushBytes(&[0xAB; 20]),
+                Instruction::Op(Opcode::EqualVerify),
+                Instruction::Op(Opcode::CheckSig),
+            ]
+        );
+        assert_eq!(
+            script.to_string(),
+            format!(
+                "OP_DUP OP_HASH160 {} OP_EQUALVERIFY OP_CHECKSIG",
+                "ab".repeat(20)
+            )
+        );
+
+        // OP_0, PUSHDATA1 and an undefined opcode.
+        let script = Script::new(vec![0x00, 0x4C, 0x02, 0xDE, 0xAD, 0xFF]);
+        assert_eq!(script.to_asm(), "OP_0 dead OP_UNKNOWN");
+
+        // PUSHDATA2 claiming more bytes than remain.
+        let malformed = Script::new(vec![0x51, 0x4D, 0x10, 0x00, 0xAA]);
+        let results: Vec<_> = malformed.instructions().collect();
+        assert_eq!(results[0], Ok(Instruction::Op(Opcode::One)));
+        assert_eq!(results[1], Err(BitcoinError::InvalidScript { offset: 1 }));
+        assert_eq!(results.len(), 2);
+        assert_eq!(malformed.to_asm(), "OP_1 [error]");
+    }
+
+    #[test]
+    fn test_script_classify() {
+        let script = |hex_script: &str| Script::new(hex::decode(hex_script).unwrap());
+        let cases = [
+            (
+                "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+                ScriptType::P2pkh,
+            ),
+            (
+                "a9148f55563b9a19f321c211e9b9f38cdf686ea0784587",
+                ScriptType::P2sh,
+            ),
+            (
+                "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                ScriptType::P2wpkh,
+            ),
+            (
+                "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+                ScriptType::P2wsh,
+            ),
+            (
+                "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+                ScriptType::P2tr,
+            ),
+            ("6a0568656c6c6f", ScriptType::OpReturn),
+            ("51", ScriptType::NonStandard),
+            (
+                "0014751e76e8199196d454941c45d1b3a323f1433b",
+                ScriptType::NonStandard,
+            ),
+        ];
+        for (hex_script, expected) in cases {
+            assert_eq!(script(hex_script).classify(), expected, "{}", hex_script);
+        }
+    }
+
+    #[test]
+    fn test_address_from_script() {
+        let address = |hex_script: &str, network| {
+            let script = Script::new(hex::decode(hex_script).unwrap());
+            Address::from_script(&script, network).map(|a| a.to_string())
+        };
+        let p2pkh = "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac";
+        let p2sh = "a9148f55563b9a19f321c211e9b9f38cdf686ea0784587";
+        let p2wpkh = "0014751e76e8199196d454941c45d1b3a323f1433bd6";
+        let p2wsh = "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262";
+        let p2tr = "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+        let expected = [
+            (
+                p2pkh,
+                Network::Mainnet,
+                "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            ),
+            (
+                p2pkh,
+                Network::Testnet,
+                "mpXwg4jMtRhuSpVq4xS3HFHmCmWp9NyGKt",
+            ),
+            (p2sh, Network::Mainnet, "3EktnHQD7RiAE6uzMj2ZifT9YgRrkSgzQX"),
+            (
+                p2sh,
+                Network::Regtest,
+                "2N6K6r2LEitDWRtYY2reSLcSQm2e2W9xEjB",
+            ),
+            (
+                p2wpkh,
+                Network::Mainnet,
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            ),
+            (
+                p2wpkh,
+                Network::Testnet,
+                "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            ),
+            (
+                p2wpkh,
+                Network::Regtest,
+                "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+            ),
+            (
+                p2wsh,
+                Network::Mainnet,
+                "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+            ),
+            (
+                p2tr,
+                Network::Mainnet,
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            ),
+        ];
+        for (hex_script, network, addr) in expected {
+            assert_eq!(address(hex_script, network).as_deref(), Some(addr));
+        }
+
+        assert_eq!(address("6a0568656c6c6f", Network::Mainnet), None);
+        assert_eq!(address("51", Network::Mainnet), None);
+
+        let script = Script::new(hex::decode(p2wpkh).unwrap());
+        let parsed = Address::from_script(&script, Network::Testnet).unwrap();
+        assert_eq!(parsed.network(), Network::Testnet);
+        assert_eq!(parsed.script_type(), ScriptType::P2wpkh);
+        assert_eq!(parsed.payload(), &script[2..]);
+    }
+
+    #[test]
+    fn test_transaction_builder_validation() {
+        let outpoint = || OutPoint::new(dummy_txid(1), 0);
+        let base = || {
+            TransactionBuilder::new(1)
+                .version(2)
+                .add_input_default_sequence(outpoint(), Script::default())
+        };
+        let reason = |result: Result<BitcoinTransaction, BitcoinError>| match result {
+            Err(BitcoinError::InvalidFormat { reason, .. }) => reason,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        let tx = base()
+            .add_output(1_000, Script::new(vec![0x51]))
+            .build()
+            .unwrap();
+        assert_eq!(tx.version, 2);
+
+        assert_eq!(
+            reason(
+                TransactionBuilder::new(2)
+                    .add_output(1, Script::default())
+                    .build()
+            ),
+            "transaction has no inputs"
+        );
+        assert_eq!(reason(base().build()), "transaction has no outputs");
+        assert_eq!(
+            reason(
+                base()
+                    .add_output(21_000_001 * 100_000_000, Script::default())
+                    .build()
+            ),
+            "output value out of range"
+        );
+        assert_eq!(
+            reason(
+                base()
+                    .add_output(20_000_000 * 100_000_000, Script::default())
+                    .add_output(2_000_000 * 100_000_000, Script::default())
+                    .build()
+            ),
+            "output value out of range"
+        );
+        assert_eq!(
+            reason(
+                base()
+                    .add_output(1, Script::new(vec![0x51; 10_001]))
+                    .build()
+            ),
+            "script exceeds size limit"
+        );
+        assert!(
+            base()
+                .add_output(1, Script::new(vec![0x51; 10_000]))
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_segwit_signature_hash_bip143_vector() {
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let script_code =
+            Script::new(hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+        let sighash = tx
+            .segwit_signature_hash(1, &script_code, 600_000_000, SighashType::All)
+            .unwrap();
+        assert_eq!(
+            hex::encode(sighash),
+            "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+        );
+
+        let cache = SighashCache::new(&tx);
+        assert_eq!(
+            cache
+                .segwit_signature_hash(1, &script_code, 600_000_000, SighashType::All)
+                .unwrap(),
+            sighash
+        );
+        assert!(
+            cache
+                .segwit_signature_hash(2, &script_code, 0, SighashType::All)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_legacy_signature_hash() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let script_code = Script::new(hex::decode("410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac").unwrap());
+        let sighash = tx
+            .signature_hash(0, &script_code, SighashType::All.to_u32())
+            .unwrap();
+        assert_eq!(
+            hex::encode(sighash),
+            "7a05c6145f10101e9d6325494245adf1297d80f8f38d4d576d57cdba220bcb19"
+        );
+
+        // OP_CODESEPARATOR is not part of the signed script code.
+        let mut with_separator = script_code.to_vec();
+        with_separator.insert(0, 0xAB);
+        assert_eq!(
+            tx.signature_hash(0, &Script::new(with_separator), SighashType::All.to_u32())
+                .unwrap(),
+            sighash
+        );
+        assert!(
+            tx.signature_hash(1, &script_code, SighashType::All.to_u32())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_legacy_sighash_single_without_matching_output() {
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let mut tx_one_output = tx.clone();
+        tx_one_output.outputs.truncate(1);
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_eq!(
+            tx_one_output
+                .signature_hash(1, &Script::default(), SighashType::Single.to_u32())
+                .unwrap(),
+            one
+        );
+        assert_ne!(
+            tx.signature_hash(1, &Script::default(), SighashType::Single.to_u32())
+                .unwrap(),
+            one
+        );
+    }
+
+    #[test]
+    fn test_sighash_type_flags() {
+        for value in [0x01, 0x02, 0x03, 0x81, 0x82, 0x83] {
+            let sighash_type = SighashType::from_u32(value).unwrap();
+            assert_eq!(sighash_type.to_u32(), value);
+            assert_eq!(sighash_type.anyone_can_pay(), value & 0x80 != 0);
+        }
+        assert_eq!(SighashType::from_u32(0x00), None);
+        assert_eq!(SighashType::from_u32(0x84), None);
+    }
+
+    const GENESIS_BLOCK_HEX: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c0101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    #[test]
+    fn test_genesis_block_roundtrip() {
+        let bytes = hex::decode(GENESIS_BLOCK_HEX).unwrap();
+        let block = Block::decode_exact(&bytes).unwrap();
+        assert_eq!(block.header.version, 1);
+        assert_eq!(block.header.time, 1231006505);
+        assert_eq!(block.header.bits, 0x1d00ffff);
+        assert_eq!(block.header.nonce, 2083236893);
+        assert_eq!(block.transactions.len(), 1);
+        assert_eq!(
+            block.block_hash().to_string(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert!(block.check_merkle_root());
+        assert_eq!(block.to_bytes(), bytes);
+
+        let mut encoded = Vec::new();
+        assert_eq!(block.consensus_encode(&mut encoded).unwrap(), bytes.len());
+        assert_eq!(encoded, bytes);
+        assert_eq!(
+            Block::consensus_decode(&mut bytes.as_slice()).unwrap(),
+            block
+        );
+
+        assert!(matches!(
+            BlockHeader::from_bytes(&bytes[..79]),
+            Err(BitcoinError::InsufficientBytes { .. })
+        ));
+    }
+
+    #[test]
+    fn test_merkle_root_pairs_and_duplicates_last() {
+        let genesis = Block::decode_exact(&hex::decode(GENESIS_BLOCK_HEX).unwrap()).unwrap();
+        let coinbase = genesis.transactions[0].clone();
+        let spend = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+
+        let mut block = Block::new(
+            genesis.header.clone(),
+            vec![coinbase.clone(), spend.clone()],
+        );
+        assert_eq!(
+            hex::encode(block.compute_merkle_root()),
+            "1de81799e61d2dc3eec98f8a1da4ca744fdbfdd3cb598e0d2b2e9d936375a801"
+        );
+        assert!(!block.check_merkle_root());
+
+        block.transactions.push(coinbase);
+        assert_eq!(
+            hex::encode(block.compute_merkle_root()),
+            "7c3c26884db6281984a861eaa5617e28fa573143d9a97d19497e79ecb6617d6b"
+        );
+        assert_eq!(
+            Block::new(genesis.header, Vec::new()).compute_merkle_root(),
+            [0; 32]
+        );
+    }
+
+    #[test]
+    fn test_error_display_and_source() {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        let err = BitcoinTransaction::from_bytes(&bytes[..116]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "truncated sequence at offset 114: needed 4 bytes, 2 available"
+        );
+        assert_eq!(
+            BitcoinTransaction::decode_exact(&[bytes.clone(), vec![0]].concat())
+                .unwrap_err()
+                .to_string(),
+            format!("invalid format at offset {}: trailing bytes", bytes.len())
+        );
+
+        let boxed: Box<dyn std::error::Error> = Box::new(err);
+        assert!(boxed.source().is_none());
+    }
+
+    #[test]
+    fn test_non_canonical_varint_offset_is_absolute() {
+        let mut bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        // Output count 2 re-encoded as FD 02 00.
+        bytes.splice(118..119, [0xFD, 0x02, 0x00]);
+        let err = BitcoinTransaction::from_bytes(&bytes).unwrap_err();
+        assert_eq!(err, BitcoinError::NonCanonicalVarInt { offset: 118 });
+        assert_eq!(err.to_string(), "non-canonical CompactSize at offset 118");
+
+        let stream_err = BitcoinTransaction::consensus_decode(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(stream_err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_version_message_decode() {
+        // Protocol 60002 version message without the BIP-37 relay flag.
+        let bytes = hex::decode("f9beb4d976657273696f6e0000000000640000003b648d5a62ea0000010000000000000011b2d05000000000010000000000000000000000000000000000ffff000000000000010000000000000000000000000000000000ffff0000000000003b2eb35d8ce617650f2f5361746f7368693a302e372e322fc03e0300").unwrap();
+        let message = NetworkMessage::decode_exact(&bytes).unwrap();
+        assert_eq!(message.magic, Network::Mainnet.magic());
+        let MessagePayload::Version(version) = &message.payload else {
+            panic!("expected a version message");
+        };
+        assert_eq!(version.version, 60002);
+        assert_eq!(version.services, 1);
+        assert_eq!(version.timestamp, 1355854353);
+        assert_eq!(version.receiver.ip[10..12], [0xFF, 0xFF]);
+        assert_eq!(version.nonce, 0x6517E68C5DB32E3B);
+        assert_eq!(version.user_agent, "/Satoshi:0.7.2/");
+        assert_eq!(version.start_height, 212672);
+        assert!(version.relay);
+
+        // Re-encoding always includes the relay flag.
+        let reencoded = message.to_bytes();
+        assert_eq!(reencoded.len(), bytes.len() + 1);
+        assert_eq!(NetworkMessage::decode_exact(&reencoded).unwrap(), message);
+    }
+
+    #[test]
+    fn test_network_message_roundtrip() {
+        let magic = Network::Regtest.magic();
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let inventory = vec![
+            Inventory::new(Inventory::TX, tx.txid().0),
+            Inventory::new(Inventory::WITNESS_BLOCK, dummy_txid(7)),
+        ];
+        let payloads = [
+            MessagePayload::Verack,
+            MessagePayload::Inv(inventory.clone()),
+            MessagePayload::GetData(inventory),
+            MessagePayload::Tx(tx.clone()),
+            MessagePayload::Ping(0x0102030405060708),
+            MessagePayload::Pong(42),
+            MessagePayload::Unknown {
+                command: "sendheaders".to_string(),
+                payload: Vec::new(),
+            },
+        ];
+        for payload in payloads {
+            let message = NetworkMessage::new(magic, payload);
+            let bytes = message.to_bytes();
+            assert_eq!(NetworkMessage::decode_exact(&bytes), Ok(message));
+        }
+
+        let verack = NetworkMessage::new(Network::Mainnet.magic(), MessagePayload::Verack);
+        assert_eq!(
+            hex::encode(verack.to_bytes()),
+            "f9beb4d976657261636b000000000000000000005df6e0e2"
+        );
+        // The dedicated tx helper frames identically.
+        assert_eq!(
+            NetworkMessage::new(magic, MessagePayload::Tx(tx.clone())).to_bytes(),
+            tx.to_p2p_message(magic)
+        );
+    }
+
+    #[test]
+    fn test_network_message_rejects_bad_framing() {
+        let message = NetworkMessage::new(Network::Mainnet.magic(), MessagePayload::Ping(1));
+        let bytes = message.to_bytes();
+
+        let mut bad_checksum = bytes.clone();
+        bad_checksum[23] ^= 0xFF;
+        assert_eq!(
+            NetworkMessage::decode_exact(&bad_checksum),
+            Err(BitcoinError::InvalidFormat {
+                offset: 20,
+                reason: "checksum mismatch",
+            })
+        );
+
+        let mut bad_command = bytes.clone();
+        bad_command[9] = b'x';
+        assert_eq!(
+            NetworkMessage::decode_exact(&bad_command),
+            Err(BitcoinError::InvalidFormat {
+                offset: 4,
+                reason: "malformed command",
+            })
+        );
+
+        assert!(matches!(
+            NetworkMessage::decode_exact(&bytes[..bytes.len() - 1]),
+            Err(BitcoinError::InsufficientBytes {
+                field: "payload",
+                offset: 24,
+                ..
+            })
+        ));
+
+        // A checksum-valid verack that carries a payload.
+        let verack =
+            NetworkMessage::new(Network::Mainnet.magic(), MessagePayload::Verack).to_bytes();
+        let framed = [
+            &verack[..16],
+            &1u32.to_le_bytes(),
+            &[0x14, 0x06, 0xE0, 0x58],
+            &[0x00],
+        ]
+        .concat();
+        assert_eq!(
+            NetworkMessage::decode_exact(&framed),
+            Err(BitcoinError::InvalidFormat {
+                offset: 24,
+                reason: "trailing bytes",
+            })
+        );
+    }
+
+    // BIP-174 test vector: one P2PKH input with a non-witness UTXO.
+    const BIP174_P2PKH_PSBT: &str = "cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQD9pQEBAAAAAAECiaPHHqtNIOA3G7ukzGmPopXJRjr6Ljl/hTPMti+VZ+UBAAAAFxYAFL4Y0VKpsBIDna89p95PUzSe7LmF/////4b4qkOnHf8USIk6UwpyN+9rRgi7st0tAXHmOuxqSJC0AQAAABcWABT+Pp7xp0XpdNkCxDVZQ6vLNL1TU/////8CAMLrCwAAAAAZdqkUhc/xCX/Z4Ai7NK9wnGIZeziXikiIrHL++E4sAAAAF6kUM5cluiHv1irHU6m80GfWx6ajnQWHAkcwRAIgJxK+IuAnDzlPVoMR3HyppolwuAJf3TskAinwf4pfOiQCIAGLONfc0xTnNMkna9b7QPZzMlvEuqFEyADS8vAtsnZcASED0uFWdJQbrUqZY3LLh+GFbTZSYG2YVi/jnF6efkE/IQUCSDBFAiEA0SuFLYXc2WHS9fSrZgZU327tzHlMDDPOXMMJ/7X85Y0CIGczio4OFyXBl/saiK9Z9R5E5CVbIBZ8hoQDHAXR8lkqASECI7cr7vCWXRC+B3jv7NYfysb3mk6haTkzgHNEZPhPKrMAAAAAAAAA";
+
+    #[test]
+    fn test_psbt_bip174_vector() {
+        let psbt = Psbt::from_base64(BIP174_P2PKH_PSBT).unwrap();
+        assert_eq!(psbt.unsigned_tx.inputs.len(), 1);
+        assert_eq!(psbt.unsigned_tx.outputs.len(), 2);
+        assert_eq!(psbt.unsigned_tx.lock_time, 1257139);
+        assert!(psbt.global.entries.is_empty());
+
+        let utxo = psbt.inputs[0].non_witness_utxo().unwrap().unwrap();
+        assert_eq!(utxo.txid(), psbt.unsigned_tx.inputs[0].previous_output.txid);
+        assert_eq!(psbt.inputs[0].witness_utxo(), Ok(None));
+        assert!(
+            psbt.outputs
+                .iter()
+                .all(|output| output.map.entries.is_empty())
+        );
+
+        assert_eq!(psbt.to_base64(), BIP174_P2PKH_PSBT);
+    }
+
+    #[test]
+    fn test_psbt_typed_fields_roundtrip() {
+        let input = TransactionInput::new(
+            OutPoint::new(dummy_txid(1), 0),
+            Script::new(vec![]),
+            0xFFFFFFFD,
+        );
+        let output = TransactionOutput::new(50_000, Script::new(vec![0x00, 0x14]));
+        let tx = BitcoinTransaction::with_outputs(2, vec![input], vec![output.clone()], 0);
+        let mut psbt = Psbt::new(tx).unwrap();
+
+        let pubkey = [0x02; 33];
+        let witness = Witness::new(vec![vec![0x30; 71], pubkey.to_vec()]);
+        psbt.inputs[0].set_witness_utxo(&output);
+        psbt.inputs[0].add_partial_sig(&pubkey, vec![0x30; 71]);
+        psbt.inputs[0]
+            .map
+            .insert(vec![0x03], SighashType::All.to_u32().to_le_bytes().to_vec());
+        psbt.inputs[0].set_final_script_witness(&witness);
+        psbt.outputs[0].map.insert(vec![0xFC, 0x01], vec![0xAB]);
+
+        let bytes = psbt.to_bytes();
+        assert_eq!(&bytes[..5], b"psbt\xff");
+        let decoded = Psbt::decode_exact(&bytes).unwrap();
+        assert_eq!(decoded, psbt);
+        assert_eq!(decoded.inputs[0].witness_utxo(), Ok(Some(output)));
+        assert_eq!(
+            decoded.inputs[0].partial_sigs(),
+            vec![(&pubkey[..], &[0x30; 71][..])]
+        );
+        assert_eq!(decoded.inputs[0].sighash_type(), Ok(Some(SighashType::All)));
+        assert_eq!(decoded.inputs[0].final_script_witness(), Ok(Some(witness)));
+        assert_eq!(decoded.inputs[0].final_script_sig(), None);
+        assert_eq!(Psbt::from_base64(&psbt.to_base64()), Ok(psbt));
+    }
+
+    #[test]
+    fn test_psbt_rejects_malformed() {
+        assert_eq!(
+            Psbt::decode_exact(b"psbx\xff\x00"),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "missing PSBT magic",
+            })
+        );
+        assert_eq!(
+            Psbt::decode_exact(b"psbt\xff\x00"),
+            Err(BitcoinError::InvalidFormat {
+                offset: 5,
+                reason: "missing unsigned transaction",
+            })
+        );
+
+        let signed = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert!(Psbt::new(signed).is_err());
+
+        let mut bytes = Psbt::from_base64(BIP174_P2PKH_PSBT).unwrap().to_bytes();
+        // Duplicate the unsigned transaction entry in the global map.
+        let global_len = 1 + 1 + 1 + 0x75;
+        let entry = bytes[5..5 + global_len].to_vec();
+        bytes.splice(5..5, entry);
+        assert_eq!(
+            Psbt::decode_exact(&bytes),
+            Err(BitcoinError::InvalidFormat {
+                offset: 5 + global_len,
+                reason: "duplicate PSBT key",
+            })
+        );
+    }
+
+    #[test]
+    fn test_borrowed_transaction_views() {
+        for tx_hex in [BLOCK_170_TX_HEX, BIP143_P2WPKH_TX_HEX] {
+            let bytes = hex::decode(tx_hex).unwrap();
+            let view = TransactionRef::decode_exact(&bytes).unwrap();
+            let owned = BitcoinTransaction::decode_exact(&bytes).unwrap();
+            assert_eq!(view.to_owned(), owned);
+            assert_eq!(view.is_segwit(), owned.is_segwit());
+
+            // Scripts point straight into the input buffer.
+            let script_sig = view.inputs[0].script_sig;
+            let start = script_sig.bytes.as_ptr() as usize - bytes.as_ptr() as usize;
+            assert_eq!(&bytes[start..start + script_sig.len()], &*script_sig);
+            assert_eq!(script_sig.to_owned(), owned.inputs[0].script_sig);
+        }
+
+        let bytes = hex::decode(BIP143_P2WPKH_TX_HEX).unwrap();
+        let view = TransactionRef::decode_exact(&bytes).unwrap();
+        assert!(view.witnesses[0].is_empty());
+        assert_eq!(view.witnesses[1].len(), 2);
+        assert_eq!(view.outputs[0].value, 112340000);
+    }
+
+    #[test]
+    fn test_borrowed_views_match_owned_errors() {
+        let bytes = hex::decode(BLOCK_170_TX_HEX).unwrap();
+        for len in [0, 3, 40, 100, bytes.len() - 1] {
+            assert_eq!(
+                TransactionRef::from_bytes(&bytes[..len]).map(|(tx, n)| (tx.to_owned(), n)),
+                BitcoinTransaction::from_bytes(&bytes[..len])
+            );
+        }
+        assert_eq!(
+            ScriptRef::decode_exact(&[0x02, 0xAB, 0xCD]),
+            Ok(ScriptRef::new(&[0xAB, 0xCD]))
+        );
+        assert_eq!(
+            WitnessRef::decode_exact(&[0x01, 0x02, 0xAB]),
+            Err(BitcoinError::InsufficientBytes {
+                field: "witness item",
+                offset: 2,
+                needed: 2,
+                available: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_decoded_matches_decoderawtransaction() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let json = serde_json::to_value(tx.to_decoded(Network::Mainnet)).unwrap();
+        let txid = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+        assert_eq!(json["txid"], txid);
+        assert_eq!(json["hash"], txid);
+        assert_eq!(json["size"], 275);
+        assert_eq!(json["vsize"], 275);
+        assert_eq!(json["weight"], 1100);
+        assert_eq!(json["locktime"], 0);
+
+        let vin = &json["vin"][0];
+        assert_eq!(
+            vin["txid"],
+            "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9"
+        );
+        assert_eq!(vin["vout"], 0);
+        assert_eq!(vin["scriptSig"]["hex"], &BLOCK_170_TX_HEX[84..228]);
+        assert_eq!(
+            vin["scriptSig"]["asm"],
+            "304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d09[ALL]"
+        );
+        assert_eq!(vin["sequence"], 4294967295u32);
+        assert!(vin.get("txinwitness").is_none());
+        assert!(vin.get("coinbase").is_none());
+
+        let vout = &json["vout"][1];
+        assert_eq!(vout["value"], 40.0);
+        assert_eq!(vout["n"], 1);
+        assert_eq!(vout["scriptPubKey"]["type"], "pubkey");
+        assert!(vout["scriptPubKey"].get("address").is_none());
+        assert_eq!(
+            vout["scriptPubKey"]["asm"],
+            "0411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3 OP_CHECKSIG"
+        );
+        assert_eq!(
+            json["vout"][0]["scriptPubKey"]["asm"],
+            "04ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84c OP_CHECKSIG"
+        );
+    }
+
+    #[test]
+    fn test_to_decoded_asm_matches_core() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let sig = "3045022100".to_string()
+            + "8b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be"
+            + "022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed";
+        let p2wpkh = hex::decode("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
+        let key_a = "03".to_string() + &"11".repeat(32);
+        let key_b = "02".to_string() + &"22".repeat(32);
+        let multisig = hex::decode(format!("5221{key_a}21{key_b}52ae")).unwrap();
+        let mut tx = segwit.clone();
+        tx.outputs = vec![
+            TransactionOutput::new(1_000, Script::new(p2wpkh)),
+            TransactionOutput::new(2_000, Script::new(multisig)),
+            TransactionOutput::new(0, Script::new(hex::decode("6a04deadbeef").unwrap())),
+        ];
+        // OP_0 dummy, a SIGHASH_NONE signature and a 4-byte number push.
+        let mut sig_none = hex::decode(&sig).unwrap();
+        sig_none.push(0x02);
+        let mut script_sig = vec![0x00, sig_none.len() as u8];
+        script_sig.extend(&sig_none);
+        script_sig.extend([0x04, 0xff, 0xff, 0xff, 0xff, 0x4f, 0x60]);
+        tx.inputs[1].script_sig = Script::new(script_sig);
+
+        let decoded = tx.to_decoded(Network::Mainnet);
+        assert_eq!(
+            decoded.vin[0].script_sig.as_ref().unwrap().asm,
+            format!("{sig}[ALL]")
+        );
+        assert_eq!(
+            decoded.vin[1].script_sig.as_ref().unwrap().asm,
+            format!("0 {sig}[NONE] -2147483647 -1 16")
+        );
+        assert_eq!(
+            decoded.vout[0].script_pubkey.asm,
+            "0 1d0f172a0ecb48aee1be1f2687d2963ae33f71a1"
+        );
+        assert_eq!(
+            decoded.vout[1].script_pubkey.asm,
+            format!("2 {key_a} {key_b} 2 OP_CHECKMULTISIG")
+        );
+        assert_eq!(decoded.vout[1].script_pubkey.script_type, "multisig");
+        assert_eq!(decoded.vout[2].script_pubkey.asm, "OP_RETURN -1874767326");
+        assert_eq!(
+            segwit.to_decoded(Network::Mainnet).vout[0]
+                .script_pubkey
+                .asm,
+            "OP_DUP OP_HASH160 8280b37df378db99f66f85c95a783a76ac7a6d59 OP_EQUALVERIFY OP_CHECKSIG"
+        );
+
+        // Signatures are only decoded in scriptSigs, and only when DER-encoded.
+        let mut not_der = hex::decode(&sig).unwrap();
+        not_der[0] = 0x31;
+        not_der.push(0x01);
+        let mut script_sig = vec![not_der.len() as u8];
+        script_sig.extend(&not_der);
+        tx.inputs[1].script_sig = Script::new(script_sig);
+        assert_eq!(
+            tx.to_decoded(Network::Mainnet).vin[1]
+                .script_sig
+                .as_ref()
+                .unwrap()
+                .asm,
+            hex::encode(&not_der)
+        );
+    }
+
+    #[test]
+    fn test_to_decoded_segwit_and_coinbase() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let decoded = segwit.to_decoded(Network::Mainnet);
+        assert_eq!(decoded.hash, segwit.wtxid().to_rpc_hex());
+        assert_ne!(decoded.hash, decoded.txid);
+        assert_eq!(decoded.size, 343);
+        assert_eq!(decoded.vsize, 261);
+        assert!(decoded.vin[0].witness.is_empty());
+        assert_eq!(decoded.vin[1].witness.len(), 2);
+        assert_eq!(decoded.vout[0].value, 1.1234);
+        assert_eq!(decoded.vout[0].script_pubkey.script_type, "pubkeyhash");
+        assert_eq!(
+            decoded.vout[0].script_pubkey.address.as_deref(),
+            Some("1Cu32FVupVCgHkMMRJdYJugxwo2Aprgk7H")
+        );
+        let json = serde_json::to_value(&decoded).unwrap();
+        assert_eq!(json["vin"][1]["txinwitness"].as_array().unwrap().len(), 2);
+
+        let genesis = Block::decode_exact(&hex::decode(GENESIS_BLOCK_HEX).unwrap()).unwrap();
+        let coinbase = genesis.transactions[0].to_decoded(Network::Mainnet);
+        let json = serde_json::to_value(&coinbase).unwrap();
+        assert_eq!(json["vin"][0]["coinbase"], &GENESIS_BLOCK_HEX[246..400]);
+        assert!(json["vin"][0].get("txid").is_none());
+        assert!(json["vin"][0].get("scriptSig").is_none());
+        assert_eq!(json["vout"][0]["value"], 50.0);
+    }
+
+    #[test]
+    fn test_counts() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(tx.counts(), (CompactSize::new(1), CompactSize::new(2)));
+
+        let input = TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::default(), 0);
+        let many = BitcoinTransaction::new(1, vec![input; 253], 0);
+        let (inputs, outputs) = many.counts();
+        assert_eq!(inputs.to_bytes(), vec![0xFD, 0xFD, 0x00]);
+        assert_eq!(outputs.to_bytes(), vec![0x00]);
+    }
+
+    #[test]
+    fn test_op_return_output() {
+        let output = op_return_output(b"hello").unwrap();
+        assert_eq!(output.value, 0);
+        assert_eq!(output.script_pubkey.bytes, b"\x6a\x05hello");
+        assert_eq!(output.script_pubkey.classify(), ScriptType::OpReturn);
+
+        let max_direct = op_return_output(&[0xAB; 75]).unwrap();
+        assert_eq!(max_direct.script_pubkey[..2], [0x6A, 0x4B]);
+        assert_eq!(max_direct.script_pubkey.len(), 77);
+
+        let pushdata1 = op_return_output(&[0xAB; 80]).unwrap();
+        assert_eq!(pushdata1.script_pubkey[..3], [0x6A, 0x4C, 0x50]);
+        assert_eq!(pushdata1.script_pubkey.len(), 83);
+
+        assert_eq!(
+            op_return_output(&[0xAB; 81]),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "OP_RETURN data exceeds 80 bytes",
+            })
+        );
+    }
+
+    #[test]
+    fn test_fee_if_output_added() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let prevout_values = [625_000_000, 600_000_000];
+        let fee = 1_225_000_000 - 112_340_000 - 223_450_000;
+        let change = TransactionOutput::new(800_000_000, Script::new(vec![0x00, 0x14]));
+
+        assert_eq!(
+            segwit.fee_if_output_added(&prevout_values, &change),
+            Ok(fee - 800_000_000)
+        );
+        assert_eq!(segwit.outputs.len(), 2);
+
+        let too_large = TransactionOutput::new(fee + 1, Script::default());
+        assert_eq!(
+            segwit.fee_if_output_added(&prevout_values, &too_large),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "outputs exceed inputs",
+            })
+        );
+        assert!(
+            segwit
+                .fee_if_output_added(&prevout_values[..1], &change)
+                .is_err()
+        );
+        let overflow = TransactionOutput::new(u64::MAX, Script::default());
+        assert_eq!(
+            segwit.fee_if_output_added(&prevout_values, &overflow),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "value overflow",
+            })
+        );
+    }
+
+    #[test]
+    fn test_all_outputs_standard() {
+        let p2wpkh = Script::new([vec![0x00, 0x14], vec![0x11; 20]].concat());
+        let mut tx = TransactionBuilder::new(2)
+            .add_input(OutPoint::new(dummy_txid(1), 0), Script::default(), 0)
+            .add_output(50_000, p2wpkh)
+            .add_output(0, op_return_output(b"memo").unwrap().script_pubkey)
+            .build()
+            .unwrap();
+        assert!(tx.all_outputs_standard());
+
+        tx.outputs
+            .push(TransactionOutput::new(1_000, Script::new(vec![0x51])));
+        assert!(!tx.outputs[2].script_pubkey.is_standard());
+        assert!(!tx.all_outputs_standard());
+
+        // Vacuously true without outputs.
+        assert!(BitcoinTransaction::new(1, vec![], 0).all_outputs_standard());
+    }
+
+    #[test]
+    fn test_to_bytes_with_flag() {
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(legacy.to_bytes_with_flag(false), legacy.to_bytes());
+
+        let forced = legacy.to_bytes_with_flag(true);
+        let plain = legacy.to_bytes();
+        assert_eq!(forced.len(), plain.len() + 3);
+        assert_eq!(forced[4..6], [0x00, 0x01]);
+        // One empty witness stack, then the lock time.
+        assert_eq!(forced[forced.len() - 5..], [0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(forced[6..forced.len() - 5], plain[4..plain.len() - 4]);
+        assert_eq!(
+            BitcoinTransaction::decode_exact(&forced),
+            Err(BitcoinError::InvalidFormat {
+                offset: forced.len() - 5,
+                reason: "superfluous witness record",
+            })
+        );
+
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(segwit.to_bytes_with_flag(true), segwit.to_bytes());
+        assert_eq!(segwit.to_bytes_with_flag(false), segwit.to_bytes());
+    }
+
+    #[test]
+    fn test_estimate_input_weight() {
+        assert_eq!(estimate_input_weight(ScriptType::P2pkh), 592);
+        assert_eq!(estimate_input_weight(ScriptType::P2sh), 364);
+        assert_eq!(estimate_input_weight(ScriptType::P2wpkh), 272);
+        assert_eq!(estimate_input_weight(ScriptType::P2wsh), 418);
+        assert_eq!(estimate_input_weight(ScriptType::P2tr), 230);
+        assert_eq!(estimate_input_weight(ScriptType::OpReturn), 0);
+        assert_eq!(estimate_input_weight(ScriptType::NonStandard), 0);
+    }
+
+    #[test]
+    fn test_is_nested_segwit() {
+        let program = [vec![0x00, 0x14], vec![0x22; 20]].concat();
+        let script_sig = Script::new([vec![0x16], program.clone()].concat());
+        let input = |script_sig: Script| {
+            TransactionInput::new(OutPoint::new(dummy_txid(1), 0), script_sig, 0xFFFFFFFF)
+        };
+        let mut tx = BitcoinTransaction::new(
+            2,
+            vec![
+                input(script_sig.clone()),
+                input(Script::new(program)),
+                input(Script::new([script_sig.bytes.clone(), vec![0x51]].concat())),
+            ],
+            0,
+        );
+        tx.witnesses = vec![Witness::new(vec![vec![0x30; 71], vec![0x02; 33]]); 3];
+        assert!(tx.is_nested_segwit(0));
+        // A bare program is not a push of one.
+        assert!(!tx.is_nested_segwit(1));
+        // Anything after the push disqualifies it.
+        assert!(!tx.is_nested_segwit(2));
+        assert!(!tx.is_nested_segwit(3));
+
+        tx.witnesses[0] = Witness::default();
+        assert!(!tx.is_nested_segwit(0));
+    }
+
+    #[test]
+    fn test_has_spendable_change_for() {
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let change = tx.outputs[1].script_pubkey.clone();
+        let unrelated = Script::new(vec![0x51]);
+        assert_eq!(
+            tx.has_spendable_change_for(&[unrelated.clone(), change]),
+            Some(1)
+        );
+        assert_eq!(tx.has_spendable_change_for(&[unrelated]), None);
+        assert_eq!(tx.has_spendable_change_for(&[]), None);
+    }
+
+    #[test]
+    fn test_vsize_delta() {
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(legacy.vsize_delta(&segwit), 275 - 261);
+        assert_eq!(segwit.vsize_delta(&legacy), 261 - 275);
+        assert_eq!(legacy.vsize_delta(&legacy), 0);
+    }
+
+    #[test]
+    fn test_package_vsize() {
+        let parent = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let child = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(package_vsize(&[parent, child]), 275 + 261);
+        assert_eq!(package_vsize(&[]), 0);
+    }
+
+    #[test]
+    fn test_suspicious_zero_outputs() {
+        let p2wpkh = Script::new([vec![0x00, 0x14], vec![0x11; 20]].concat());
+        let tx = BitcoinTransaction::with_outputs(
+            2,
+            vec![],
+            vec![
+                TransactionOutput::new(0, p2wpkh.clone()),
+                op_return_output(b"memo").unwrap(),
+                TransactionOutput::new(1_000, p2wpkh),
+                TransactionOutput::new(0, Script::default()),
+            ],
+            0,
+        );
+        assert_eq!(tx.suspicious_zero_outputs(), vec![0, 3]);
+    }
+
+    #[test]
+    fn test_outputs_bytes() {
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let bytes = tx.outputs_bytes();
+        assert_eq!(
+            bytes,
+            [tx.outputs[0].to_bytes(), tx.outputs[1].to_bytes()].concat()
+        );
+        // The two outputs sit between the inputs and the witnesses.
+        let start = BIP143_P2WPKH_TX_HEX.find("202cb206").unwrap();
+        assert_eq!(
+            hex::encode(&bytes),
+            BIP143_P2WPKH_TX_HEX[start..start + 2 * bytes.len()]
+        );
+        assert!(
+            BitcoinTransaction::new(1, vec![], 0)
+                .outputs_bytes()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_simple_spend() {
+        let prevout = OutPoint::new(dummy_txid(3), 1);
+        let dest = Script::new([vec![0x00, 0x14], vec![0x11; 20]].concat());
+        let tx = simple_spend(prevout.clone(), 100_000, dest.clone(), 1_000).unwrap();
+        assert_eq!(tx.inputs.len(), 1);
+        assert_eq!(tx.inputs[0].previous_output, prevout);
+        assert!(tx.inputs[0].script_sig.is_empty());
+        assert_eq!(
+            tx.outputs,
+            vec![TransactionOutput::new(99_000, dest.clone())]
+        );
+        assert_eq!(
+            tx.fee(&[TransactionOutput::new(100_000, Script::default())]),
+            Ok(1_000)
+        );
+
+        assert_eq!(
+            simple_spend(prevout, 999, dest, 1_000),
+            Err(BitcoinError::InvalidFormat {
+                offset: 0,
+                reason: "fee exceeds prevout value",
+            })
+        );
+    }
+
+    #[test]
+    fn test_has_output_address_reuse() {
+        let mut tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert!(!tx.has_output_address_reuse());
+
+        let reused = tx.outputs[0].script_pubkey.clone();
+        tx.outputs.push(TransactionOutput::new(1, reused));
+        assert!(tx.has_output_address_reuse());
+        assert!(!BitcoinTransaction::new(1, vec![], 0).has_output_address_reuse());
+    }
+
+    #[test]
+    fn test_size_without_change() {
+        for tx_hex in [BLOCK_170_TX_HEX, BIP143_P2WPKH_TX_HEX] {
+            let tx = BitcoinTransaction::from_hex(tx_hex).unwrap();
+            let mut without = tx.clone();
+            without.outputs.pop();
+            assert_eq!(tx.size_without_change(), without.serialized_size());
+        }
+
+        // Dropping the 253rd output also shrinks the output count.
+        let outputs = vec![TransactionOutput::new(1, Script::default()); 253];
+        let tx = BitcoinTransaction::with_outputs(1, vec![], outputs, 0);
+        assert_eq!(tx.size_without_change(), tx.serialized_size() - 9 - 2);
+
+        let empty = BitcoinTransaction::new(1, vec![], 0);
+        assert_eq!(empty.size_without_change(), empty.serialized_size());
+    }
+
+    #[test]
+    fn test_outputs_by_value_desc() {
+        let output = |value| TransactionOutput::new(value, Script::new(vec![0x51]));
+        let tx = BitcoinTransaction::with_outputs(
+            2,
+            vec![],
+            vec![output(500), output(9_000), output(500), output(70_000)],
+            0,
+        );
+        let sorted: Vec<_> = tx
+            .outputs_by_value_desc()
+            .into_iter()
+            .map(|(i, output)| (i, output.value))
+            .collect();
+        assert_eq!(sorted, vec![(3, 70_000), (1, 9_000), (0, 500), (2, 500)]);
+        assert!(std::ptr::eq(
+            tx.outputs_by_value_desc()[0].1,
+            &tx.outputs[3]
+        ));
+    }
+
+    #[test]
+    fn test_fee_breakdown() {
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let (base, witness) = segwit.fee_breakdown(10_420);
+        // 932 of the 1042 WU are base data, 110 are witness.
+        assert_eq!(base, 9_320.0);
+        assert_eq!(witness, 1_100.0);
+
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        assert_eq!(legacy.fee_breakdown(5_000), (5_000.0, 0.0));
+    }
+
+    #[test]
+    fn test_is_child_of() {
+        let parent = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let child = simple_spend(
+            OutPoint::new(parent.txid().0, 1),
+            3_000_000_000,
+            Script::new(vec![0x51]),
+            1_000,
+        )
+        .unwrap();
+        assert!(child.is_child_of(&parent));
+        assert!(!parent.is_child_of(&child));
+
+        let unrelated = simple_spend(
+            OutPoint::new(dummy_txid(1), 1),
+            3_000_000_000,
+            Script::new(vec![0x51]),
+            1_000,
+        )
+        .unwrap();
+        assert!(!unrelated.is_child_of(&parent));
+    }
+
+    #[test]
+    fn test_spent_output_indices_of() {
+        let parent = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let child = BitcoinTransaction::build_unsigned(
+            vec![
+                OutPoint::new(parent.txid().0, 1),
+                OutPoint::new(dummy_txid(1), 0),
+                OutPoint::new(parent.txid().0, 0),
+            ],
+            vec![(1_000, Script::new(vec![0x51]))],
+            0,
+        );
+        assert_eq!(child.spent_output_indices_of(&parent), vec![1, 0]);
+        assert!(parent.spent_output_indices_of(&child).is_empty());
+    }
+
+    #[test]
+    fn test_validate_witness_items() {
+        let mut tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(tx.validate_witness_items(), Ok(()));
+
+        tx.witnesses[1] = Witness::new(vec![vec![0; 520]]);
+        assert_eq!(tx.validate_witness_items(), Ok(()));
+
+        tx.witnesses[1] = Witness::new(vec![vec![0x30; 72], vec![0; 521]]);
+        assert!(matches!(
+            tx.validate_witness_items(),
+            Err(BitcoinError::InvalidFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sigop_count() {
+        let p2pkh =
+            Script::new(hex::decode("76a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba88ac").unwrap());
+        assert_eq!(p2pkh.sigop_count(false), 1);
+
+        let mut multisig = vec![0x52];
+        for _ in 0..3 {
+            multisig.push(33);
+            multisig.extend([0x02; 33]);
+        }
+        multisig.extend([0x53, 0xAE]);
+        let multisig = Script::new(multisig);
+        assert_eq!(multisig.sigop_count(true), 3);
+        // The legacy count charges every CHECKMULTISIG 20.
+        assert_eq!(multisig.sigop_count(false), 20);
+
+        // Without a preceding key count, CHECKMULTISIG is charged 20 either way.
+        assert_eq!(Script::new(vec![0xAE, 0xAC, 0xAD]).sigop_count(true), 22);
+        // Opcode bytes inside pushed data are not counted.
+        assert_eq!(Script::new(vec![0x02, 0xAC, 0xAE]).sigop_count(false), 0);
+    }
+
+    #[test]
+    fn test_adjusted_vsize() {
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let prevouts = [legacy.outputs[0].script_pubkey.clone()];
+        assert_eq!(legacy.adjusted_vsize(&prevouts), Ok(legacy.vsize()));
+        assert!(legacy.adjusted_vsize(&[]).is_err());
+
+        // 10 bare CHECKMULTISIGs cost 200 sigops, or 4000 vbytes.
+        let heavy = BitcoinTransaction::build_unsigned(
+            vec![OutPoint::new(dummy_txid(1), 0)],
+            vec![(1_000, Script::new(vec![0xAE; 10]))],
+            0,
+        );
+        assert_eq!(heavy.adjusted_vsize(&prevouts), Ok(4_000));
+
+        // Bare OP_1 <key> OP_1 OP_CHECKMULTISIG outputs are still charged 20
+        // sigops each by the legacy count.
+        let mut one_of_one = vec![0x51, 33];
+        one_of_one.extend([0x02; 33]);
+        one_of_one.extend([0x51, 0xAE]);
+        assert_eq!(Script::new(one_of_one.clone()).sigop_count(false), 20);
+        let bare_multisig = BitcoinTransaction::build_unsigned(
+            vec![OutPoint::new(dummy_txid(1), 0)],
+            vec![(1_000, Script::new(one_of_one)); 10],
+            0,
+        );
+        assert_eq!(bare_multisig.adjusted_vsize(&prevouts), Ok(4_000));
+
+        // A P2SH spend is charged for the sigops of its redeem script.
+        let mut redeem_script = vec![0xAC; 60];
+        redeem_script.extend([0xAE; 2]);
+        let mut script_sig = vec![0x4C, redeem_script.len() as u8];
+        script_sig.extend(&redeem_script);
+        let p2sh_spend = BitcoinTransaction::with_outputs(
+            1,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(2), 0),
+                Script::new(script_sig),
+                0xFFFFFFFF,
+            )],
+            vec![TransactionOutput::new(1_000, Script::new(vec![0x51]))],
+            0,
+        );
+        let mut p2sh = vec![0xA9, 0x14];
+        p2sh.extend([0; 20]);
+        p2sh.push(0x87);
+        // The redeem script is pushed data, so only the P2SH count applies.
+        let p2sh = [Script::new(p2sh)];
+        assert_eq!(p2sh_spend.adjusted_vsize(&p2sh), Ok(2_000));
+        assert_eq!(p2sh_spend.adjusted_vsize(&prevouts), Ok(p2sh_spend.vsize()));
+
+        // A scriptSig that is not push-only never reaches the redeem script.
+        let mut not_push_only = p2sh_spend.clone();
+        let mut script_sig = vec![0x61];
+        script_sig.extend(p2sh_spend.inputs[0].script_sig.iter());
+        not_push_only.inputs[0].script_sig = Script::new(script_sig);
+        assert_eq!(
+            not_push_only.adjusted_vsize(&p2sh),
+            Ok(not_push_only.vsize())
+        );
+    }
+
+    #[test]
+    fn test_without_witness_clone() {
+        let tx = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let stripped = tx.without_witness_clone();
+        assert!(!stripped.is_segwit());
+        assert!(stripped.witnesses.is_empty());
+        assert_eq!(stripped.to_bytes(), tx.to_legacy_bytes());
+        assert_eq!(stripped.txid(), tx.txid());
+        assert_eq!(stripped.wtxid().0, tx.txid().0);
+        assert!(tx.is_segwit());
+    }
+
+    #[test]
+    fn test_validate_coinbase_value() {
+        let genesis = Block::decode_exact(&hex::decode(GENESIS_BLOCK_HEX).unwrap()).unwrap();
+        let mut coinbase = genesis.transactions[0].clone();
+        assert_eq!(coinbase.validate_coinbase_value(0, 0), Ok(()));
+        // The same payout is too much once the subsidy has halved.
+        assert!(coinbase.validate_coinbase_value(210_000, 0).is_err());
+        assert_eq!(
+            coinbase.validate_coinbase_value(210_000, 25_0000_0000),
+            Ok(())
+        );
+
+        coinbase.outputs[0].value += 1;
+        assert!(matches!(
+            coinbase.validate_coinbase_value(0, 0),
+            Err(BitcoinError::InvalidFormat { .. })
+        ));
+        assert_eq!(coinbase.validate_coinbase_value(0, 1), Ok(()));
+        assert!(coinbase.validate_coinbase_value(0, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_analyze() {
+        let bytes = hex::decode(BIP143_P2WPKH_TX_HEX).unwrap();
+        let analysis = BitcoinTransaction::analyze(&bytes).unwrap();
+        assert_eq!(
+            analysis.txid.to_rpc_hex(),
+            "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609"
+        );
+        assert_eq!(
+            analysis.wtxid.to_rpc_hex(),
+            "c36c38370907df2324d9ce9d149d191192f338b37665a82e78e76a12c909b762"
+        );
+        assert_eq!(analysis.size, 343);
+        assert_eq!(analysis.vsize, 261);
+        assert_eq!(analysis.weight, 1042);
+        assert_eq!(analysis.input_count, 2);
+        assert_eq!(analysis.output_count, 2);
+        assert!(analysis.is_segwit);
+        assert!(!analysis.is_coinbase);
+        // The first input's sequence is 0xffffffee.
+        assert!(analysis.is_rbf);
+
+        let genesis = Block::decode_exact(&hex::decode(GENESIS_BLOCK_HEX).unwrap()).unwrap();
+        let coinbase = BitcoinTransaction::analyze(&genesis.transactions[0].to_bytes()).unwrap();
+        assert!(coinbase.is_coinbase);
+        assert!(!coinbase.is_segwit);
+        assert!(!coinbase.is_rbf);
+        assert_eq!(coinbase.wtxid.0, coinbase.txid.0);
+
+        assert!(BitcoinTransaction::analyze(&bytes[..100]).is_err());
+    }
+
+    #[test]
+    fn test_is_coinbase() {
+        let genesis = Block::decode_exact(&hex::decode(GENESIS_BLOCK_HEX).unwrap()).unwrap();
+        let mut coinbase = genesis.transactions[0].clone();
+        assert!(coinbase.is_coinbase());
+        assert!(
+            !BitcoinTransaction::from_hex(BLOCK_170_TX_HEX)
+                .unwrap()
+                .is_coinbase()
+        );
+
+        coinbase.inputs.push(coinbase.inputs[0].clone());
+        assert!(!coinbase.is_coinbase());
+    }
+
+    #[test]
+    fn test_fee_rate_cmp() {
+        use std::cmp::Ordering;
+
+        let legacy = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        let legacy_vsize = legacy.vsize() as u64;
+
+        // 1 sat/vB each.
+        assert_eq!(
+            legacy.fee_rate_cmp(legacy_vsize, &segwit, 261),
+            Ordering::Equal
+        );
+        assert_eq!(
+            legacy.fee_rate_cmp(legacy_vsize, &segwit, 262),
+            Ordering::Less
+        );
+        assert_eq!(
+            segwit.fee_rate_cmp(262, &legacy, legacy_vsize),
+            Ordering::Greater
+        );
+
+        // These rates are equal as f64 but not as integers.
+        assert_eq!(
+            legacy.fee_rate_cmp(u64::MAX, &legacy, u64::MAX - 1),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_distinct_output_types() {
+        // Both outputs are P2PKH.
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        assert_eq!(segwit.distinct_output_types(), 1);
+
+        let mut p2wpkh = vec![0x00, 0x14];
+        p2wpkh.extend([0x11; 20]);
+        let mixed = BitcoinTransaction::build_unsigned(
+            vec![OutPoint::new(dummy_txid(1), 0)],
+            vec![
+                (1_000, segwit.outputs[0].script_pubkey.clone()),
+                (2_000, Script::new(p2wpkh)),
+                (3_000, segwit.outputs[1].script_pubkey.clone()),
+                (0, op_return_output(b"hi").unwrap().script_pubkey),
+            ],
+            0,
+        );
+        assert_eq!(mixed.distinct_output_types(), 3);
+
+        let no_outputs = BitcoinTransaction::new(2, Vec::new(), 0);
+        assert_eq!(no_outputs.distinct_output_types(), 0);
+    }
+
+    #[test]
+    fn test_estimated_signed_vsize() {
+        let p2pkh = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX)
+            .unwrap()
+            .outputs[0]
+            .script_pubkey
+            .clone();
+        let tx = BitcoinTransaction::build_unsigned(
+            vec![
+                OutPoint::new(dummy_txid(1), 0),
+                OutPoint::new(dummy_txid(2), 1),
+            ],
+            vec![(1_000, p2pkh)],
+            0,
+        );
+        // 176 WU of skeleton, 592 + 272 for the inputs, 2 for marker and
+        // flag and 1 for the p2pkh input's empty witness.
+        assert_eq!(
+            tx.estimated_signed_vsize(&[ScriptType::P2pkh, ScriptType::P2wpkh]),
+            Ok(261)
+        );
+        // Without witness inputs there is no marker, flag or empty witness.
+        assert_eq!(
+            tx.estimated_signed_vsize(&[ScriptType::P2pkh, ScriptType::P2pkh]),
+            Ok(340)
+        );
+        assert!(tx.estimated_signed_vsize(&[ScriptType::P2wpkh]).is_err());
+        assert!(
+            tx.estimated_signed_vsize(&[ScriptType::P2wpkh, ScriptType::OpReturn])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_minimal_spend_size() {
+        assert_eq!(minimal_spend_size(ScriptType::P2pkh), 189);
+        assert_eq!(minimal_spend_size(ScriptType::P2sh), 133);
+        assert_eq!(minimal_spend_size(ScriptType::P2wpkh), 110);
+        assert_eq!(minimal_spend_size(ScriptType::P2wsh), 146);
+        assert_eq!(minimal_spend_size(ScriptType::P2tr), 99);
+        assert_eq!(minimal_spend_size(ScriptType::OpReturn), 0);
+        assert_eq!(minimal_spend_size(ScriptType::NonStandard), 0);
+    }
+
+    #[test]
+    fn test_sighash_preimage() {
+        use sha2::{Digest, Sha256};
+        let sha256d = |bytes: &[u8]| -> [u8; 32] { Sha256::digest(Sha256::digest(bytes)).into() };
+
+        let tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let script_code = tx.outputs[1].script_pubkey.clone();
+        let preimage = tx
+            .sighash_preimage(0, &script_code, SighashType::All.to_u32())
+            .unwrap();
+        let mut signed = tx.clone();
+        signed.inputs[0].script_sig = script_code.clone();
+        let mut expected = signed.to_legacy_bytes();
+        expected.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]);
+        assert_eq!(preimage, expected);
+
+        let segwit = BitcoinTransaction::from_hex(BIP143_P2WPKH_TX_HEX).unwrap();
+        // The defined types plus non-standard hashtypes legacy signatures may carry.
+        for sighash_type in [
+            0x01,
+            0x02,
+            0x03,
+            0x81,
+            0x82,
+            0x83,
+            0x00,
+            0x04,
+            0x43,
+            0x1234_5601,
+        ] {
+            for input_index in 0..2 {
+                let preimage = segwit
+                    .sighash_preimage(input_index, &script_code, sighash_type)
+                    .unwrap();
+                assert_eq!(
+                    sha256d(&preimage),
+                    segwit
+                        .signature_hash(input_index, &script_code, sighash_type)
+                        .unwrap()
+                );
+            }
+        }
+
+        let mut one_output = segwit.clone();
+        one_output.outputs.truncate(1);
+        assert!(
+            one_output
+                .sighash_preimage(1, &script_code, SighashType::Single.to_u32())
+                .is_err()
+        );
+        assert!(
+            segwit
+                .sighash_preimage(2, &script_code, SighashType::All.to_u32())
+                .is_err()
+        );
+
+        // Undefined base types sign like ALL but commit to the full 32-bit value.
+        let all = segwit.sighash_preimage(0, &script_code, 0x01).unwrap();
+        for sighash_type in [0x00, 0x04, 0x1F, 0x1234_5601] {
+            let preimage = segwit
+                .sighash_preimage(0, &script_code, sighash_type)
+                .unwrap();
+            let split = preimage.len() - 4;
+            assert_eq!(preimage[..split], all[..split]);
+            assert_eq!(preimage[split..], sighash_type.to_le_bytes());
+        }
+        // 0x43 is SINGLE with an unknown flag bit.
+        let single = segwit.sighash_preimage(0, &script_code, 0x03).unwrap();
+        let flagged = segwit.sighash_preimage(0, &script_code, 0x43).unwrap();
+        assert_eq!(flagged[..flagged.len() - 4], single[..single.len() - 4]);
+    }
+
+    #[test]
+    fn test_is_segwit_ignores_surplus_witnesses() {
+        let mut tx = BitcoinTransaction::from_hex(BLOCK_170_TX_HEX).unwrap();
+        let legacy_bytes = tx.to_bytes();
+        tx.witnesses = vec![Witness::default(), Witness::new(vec![vec![0x01]])];
+        assert!(!tx.is_segwit());
+        assert_eq!(tx.to_bytes(), legacy_bytes);
+        assert_eq!(tx.weight(), 4 * legacy_bytes.len());
+
+        let mut encoded = Vec::new();
+        tx.consensus_encode(&mut encoded).unwrap();
+        assert_eq!(encoded, legacy_bytes);
+
+        let mut borrowed = TransactionRef::decode_exact(&legacy_bytes).unwrap();
+        borrowed.witnesses = vec![
+            WitnessRef::default(),
+            WitnessRef {
+                items: vec![&[0x01]],
+            },
+        ];
+        assert!(!borrowed.is_segwit());
+    }
+
+    #[test]
+    fn test_is_push_only() {
+        assert!(Script::new(vec![0x00, 0x02, 0xAA, 0xBB, 0x4F, 0x51, 0x60]).is_push_only());
+        assert!(Script::default().is_push_only());
+        assert!(!Script::new(vec![0x51, 0x61]).is_push_only());
+        // A truncated push does not parse.
+        assert!(!Script::new(vec![0x02, 0xAA]).is_push_only());
+    }
 }